@@ -0,0 +1,157 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use rust_sc2::prelude::UnitTypeId as U;
+
+/// Who our ranged units shoot first. Anything not listed gets `DEFAULT_PRIORITY`, structures
+/// that can't fight back get 0.
+const TARGET_PRIORITIES: &[(UnitTypeId, i32)] = &[
+    // Splash and spellcasters that wreck clumps
+    (U::SiegeTankSieged, 10),
+    (U::Colossus, 9),
+    (U::HighTemplar, 9),
+    (U::Disruptor, 9),
+    (U::Baneling, 9),
+    (U::WidowMineBurrowed, 8),
+    (U::WidowMine, 8),
+    (U::SiegeTank, 8),
+    (U::Infestor, 8),
+    (U::Medivac, 7),
+    (U::Immortal, 7),
+    (U::LurkerMP, 7),
+    (U::LurkerMPBurrowed, 7),
+    // Core army
+    (U::Marine, 5),
+    (U::Marauder, 5),
+    (U::Stalker, 5),
+    (U::Adept, 5),
+    (U::Archon, 5),
+    (U::Roach, 5),
+    (U::Hydralisk, 5),
+    (U::Zealot, 4),
+    (U::Queen, 4),
+    (U::Zergling, 3),
+    // Static defense that shoots back
+    (U::PhotonCannon, 4),
+    (U::Bunker, 4),
+    (U::SpineCrawler, 4),
+    (U::SporeCrawler, 2),
+    (U::MissileTurret, 2),
+    // Workers are free kills, but not worth ignoring the army for
+    (U::Probe, 2),
+    (U::SCV, 2),
+    (U::Drone, 2),
+];
+const DEFAULT_PRIORITY: i32 = 3;
+
+/// Units that get individual micro
+const MICRO_UNITS: &[UnitTypeId] = &[U::Roach, U::Hydralisk];
+/// Enemies further away than this leave the unit to the army's attack-move
+const ENGAGE_RADIUS: f32 = 12.0;
+/// Roaches below this fall back to regenerate, and rejoin the army once above `PULLBACK_RECOVERED`
+const PULLBACK_HEALTH: f32 = 0.3;
+const PULLBACK_RECOVERED: f32 = 0.5;
+/// Enemies with less range than this are melee for kiting purposes
+const MELEE_RANGE: f32 = 1.5;
+const KITE_DISTANCE: f32 = 2.0;
+
+fn target_priority(unit: &Unit) -> i32 {
+    if let Some(&(_, priority)) = TARGET_PRIORITIES.iter().find(|(t, _)| *t == unit.type_id()) {
+        priority
+    } else if unit.is_structure() {
+        0
+    } else {
+        DEFAULT_PRIORITY
+    }
+}
+
+impl FaxBot {
+    /// Highest priority target in range, going for the one closest to dying among equals
+    fn choose_focus_target<'a>(&self, unit: &Unit, enemies: &'a Units) -> Option<&'a Unit> {
+        enemies
+            .iter()
+            .filter(|e| unit.can_attack_unit(e) && unit.in_range(e, 0.0))
+            .max_by_key(|e| {
+                let hits = e.health().unwrap_or(0) + e.shield().unwrap_or(0);
+                (target_priority(e), std::cmp::Reverse(hits))
+            })
+    }
+
+    /// Sends badly damaged roaches back to the closest defensive position, which is on creep and
+    /// still helps hold it, until they've healed. Not needed once roaches can heal burrowed.
+    fn pull_back_damaged(&mut self, unit: &Unit) -> bool {
+        if unit.type_id() != UnitTypeId::Roach || self.can_burrow_roaches() {
+            return false;
+        }
+        let health = unit.health_percentage().unwrap_or(1.0);
+        let pulled_back = &mut self.state.micro.pulled_back;
+        if health < PULLBACK_HEALTH {
+            pulled_back.insert(unit.tag());
+        } else if health >= PULLBACK_RECOVERED {
+            pulled_back.remove(&unit.tag());
+        }
+        if !pulled_back.contains(&unit.tag()) {
+            return false;
+        }
+        let home = self
+            .state
+            .micro
+            .defense
+            .positions
+            .iter()
+            .closest(unit.position())
+            .copied()
+            .unwrap_or(self.start_location);
+        if unit.distance(home) > 8.0 {
+            unit.move_to(Target::Pos(home), false);
+        }
+        true
+    }
+
+    /// Steps away from melee units while the weapon reloads
+    fn kite(&self, unit: &Unit, enemies: &Units) -> bool {
+        if unit.weapon_cooldown().is_none_or(|c| c <= 0.0) {
+            return false;
+        }
+        let melee = enemies.filter(|e| {
+            e.can_attack_ground()
+                && e.ground_range() < MELEE_RANGE
+                && e.distance(unit) <= e.ground_range() + e.radius() + unit.radius() + 1.5
+        });
+        match melee.closest(unit) {
+            Some(enemy) => {
+                let away = (unit.position() - enemy.position()).normalize();
+                unit.move_to(Target::Pos(unit.position() + away * KITE_DISTANCE), false);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Per unit micro for ranged units near enemies: damaged roaches pull back, units kite melee
+    /// while reloading, and otherwise focus the best target in range. `busy` units are left alone.
+    pub fn micro_army(&mut self, busy: &[u64]) {
+        let units = self.units.my.units.filter(|u| MICRO_UNITS.contains(&u.type_id()));
+        let living = units.tags().copied().collect::<Vec<_>>();
+        self.state.micro.pulled_back.retain(|t| living.contains(t));
+        let enemies = self.units.enemy.all.filter(|e| e.is_visible() && !e.is_snapshot());
+        for unit in units.iter().filter(|u| !busy.contains(&u.tag())) {
+            if self.pull_back_damaged(unit) {
+                continue;
+            }
+            let nearby = enemies.closer(ENGAGE_RADIUS, unit);
+            if nearby.is_empty() || self.kite(unit, &nearby) {
+                continue;
+            }
+            if let Some(target) = self.choose_focus_target(unit, &nearby) {
+                if unit.target_tag() != Some(target.tag()) {
+                    unit.attack(Target::Tag(target.tag()), false);
+                }
+            } else if unit.is_idle() {
+                if let Some(closest) = nearby.closest(unit) {
+                    unit.attack(Target::Pos(closest.position()), false);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use rust_sc2::prelude::UpgradeId as Up;
+
+/// Game seconds at which we attack with whatever we have, unless told otherwise on the command line
+pub const DEFAULT_ATTACK_WAVES: &[f32] = &[330.0, 480.0, 660.0];
+/// Army supply needed for a wave or an upgrade timing to be worth it
+const MIN_ATTACK_SUPPLY: f32 = 16.0;
+/// Army supply at which we attack no matter what
+const ATTACK_SUPPLY: f32 = 60.0;
+/// Total supply at which we're close enough to maxed to trade armies
+const MAXED_SUPPLY: u32 = 150;
+/// How much bigger our army has to be than theirs to attack on that alone
+const ADVANTAGE_RATIO: f32 = 1.5;
+/// Share of the army that has to be at the staging point before we go
+const GATHERED_FRACTION: f32 = 0.8;
+const GATHER_RADIUS: f32 = 12.0;
+const GATHER_TIMEOUT: usize = 22 * 20;
+/// We retreat once the army is down to this share of what we attacked with and we're behind
+const RETREAT_FRACTION: f32 = 0.5;
+const RETREAT_TIMEOUT: usize = 22 * 20;
+/// Army units further than this from every defensive position go back to one
+const HOME_RADIUS: f32 = 16.0;
+/// How long an attack target is kept before it's chosen again
+const TARGET_REFRESH: usize = 22 * 5;
+
+/// Upgrades that make the army noticeably stronger the moment they finish
+const COMBAT_UPGRADES: &[UpgradeId] = &[
+    Up::ZergMissileWeaponsLevel1,
+    Up::ZergMissileWeaponsLevel2,
+    Up::ZergMissileWeaponsLevel3,
+    Up::ZergMeleeWeaponsLevel1,
+    Up::ZergMeleeWeaponsLevel2,
+    Up::ZergMeleeWeaponsLevel3,
+    Up::ZergGroundArmorsLevel1,
+    Up::ZergGroundArmorsLevel2,
+    Up::ZergGroundArmorsLevel3,
+    Up::Zerglingattackspeed,
+    Up::GlialReconstitution,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArmyMode {
+    /// Holding the defensive positions, or fighting off an attack
+    Defending,
+    /// Walking to the staging point so we attack together
+    Gathering { staging: Point2 },
+    Attacking { supply: f32 },
+    /// Walking home without fighting
+    Retreating,
+}
+
+/// What the army as a whole is doing, and what has already made us attack
+#[derive(Debug)]
+pub struct AttackState {
+    pub mode: ArmyMode,
+    changed_at: usize,
+    /// Where idle attacking units go, and when that was chosen
+    pub target: Option<(Point2, usize)>,
+    waves_sent: usize,
+    upgrades_seen: usize,
+}
+
+impl Default for AttackState {
+    fn default() -> Self {
+        AttackState {
+            mode: ArmyMode::Defending,
+            changed_at: 0,
+            target: None,
+            waves_sent: 0,
+            upgrades_seen: 0,
+        }
+    }
+}
+
+impl FaxBot {
+    /// Army units that follow the attack state, leaving out runbys, units still waiting to walk
+    /// out and damaged units pulled back until they heal
+    pub fn attack_army(&self) -> Units {
+        self.units.my.units.filter(|u| {
+            !u.is_worker()
+                && u.can_attack()
+                && !matches!(u.type_id(), UnitTypeId::Queen | UnitTypeId::Overseer)
+                && !u.is_burrowed()
+                && !self.state.micro.runby.contains(u.tag())
+                && !self.state.micro.reinforcements.contains(u.tag())
+                && !self.state.micro.pulled_back.contains(&u.tag())
+        })
+    }
+
+    fn enemy_army_supply(&self) -> f32 {
+        self.recent_enemy_army()
+            .iter()
+            .map(|(_, t)| self.get_unit_cost(*t).supply)
+            .sum()
+    }
+
+    /// Rough fight estimate: army supply squared, since bigger armies lose less while trading
+    fn army_advantage(&self) -> f32 {
+        let ours = self.army_supply();
+        let theirs = self.enemy_army_supply().max(1.0);
+        (ours / theirs).powi(2)
+    }
+
+    fn waves_due(&self) -> usize {
+        self.runtime_options
+            .attack_waves
+            .iter()
+            .filter(|&&at| self.time >= at)
+            .count()
+    }
+
+    fn combat_upgrades_done(&self) -> usize {
+        COMBAT_UPGRADES.iter().filter(|&&u| self.has_upgrade(u)).count()
+    }
+
+    /// Why we should attack now, if we should. Waves and upgrades stay due until we attack,
+    /// however many came due meanwhile.
+    fn attack_trigger(&self) -> Option<&'static str> {
+        let supply = self.army_supply();
+        let new_upgrade = self.combat_upgrades_done() > self.state.attack.upgrades_seen;
+        let wave_due = self.waves_due() > self.state.attack.waves_sent;
+        if supply >= ATTACK_SUPPLY || self.supply_used >= MAXED_SUPPLY {
+            Some("supply")
+        } else if supply < MIN_ATTACK_SUPPLY {
+            None
+        } else if wave_due {
+            Some("timing wave")
+        } else if new_upgrade {
+            Some("upgrade")
+        } else if self.army_advantage() >= ADVANTAGE_RATIO.powi(2) {
+            Some("army advantage")
+        } else {
+            None
+        }
+    }
+
+    /// The defensive position closest to the enemy
+    fn staging_point(&self) -> Point2 {
+        self.state
+            .micro
+            .defense
+            .positions
+            .iter()
+            .closest(self.enemy_start)
+            .copied()
+            .unwrap_or(self.start_location)
+    }
+
+    fn set_army_mode(&mut self, mode: ArmyMode, reason: &str) {
+        println!(
+            "{}: Army {:?} -> {:?} ({})",
+            self.current_iteration, self.state.attack.mode, mode, reason
+        );
+        self.state.attack.mode = mode;
+        self.state.attack.changed_at = self.current_iteration;
+        self.state.attack.target = None;
+        // Only an attack that actually goes out uses up the waves and upgrades that are due
+        if let ArmyMode::Attacking { .. } = mode {
+            self.state.attack.waves_sent = self.waves_due();
+            self.state.attack.upgrades_seen = self.combat_upgrades_done();
+        }
+    }
+
+    /// Picks the attack target again once it's stale, but only when idle units need one since
+    /// it queries the pathing
+    fn refresh_attack_target(&mut self) {
+        let iteration = self.current_iteration;
+        let is_stale = self
+            .state
+            .attack
+            .target
+            .is_none_or(|(_, chosen_at)| iteration >= chosen_at + TARGET_REFRESH);
+        if is_stale && !self.attack_army().idle().is_empty() {
+            self.state.attack.target = Some((self.choose_attack_target(), iteration));
+        }
+    }
+
+    /// Decides whether we defend, gather, attack or retreat
+    pub fn update_army_mode(&mut self) {
+        let iteration = self.current_iteration;
+        let elapsed = iteration - self.state.attack.changed_at;
+        let supply = self.army_supply();
+        match self.state.attack.mode {
+            ArmyMode::Defending => {
+                if self.state.is_under_attack {
+                    return;
+                }
+                if let Some(reason) = self.attack_trigger() {
+                    let staging = self.staging_point();
+                    self.set_army_mode(ArmyMode::Gathering { staging }, reason);
+                }
+            }
+            ArmyMode::Gathering { staging } => {
+                let army = self.attack_army();
+                let gathered = army.closer(GATHER_RADIUS, staging).len();
+                if self.state.is_under_attack {
+                    self.set_army_mode(ArmyMode::Defending, "under attack");
+                } else if gathered as f32 >= army.len() as f32 * GATHERED_FRACTION || elapsed >= GATHER_TIMEOUT {
+                    self.set_army_mode(ArmyMode::Attacking { supply }, "gathered");
+                }
+            }
+            ArmyMode::Attacking { supply: attacked_with } => {
+                let advantage = self.army_advantage();
+                if supply < attacked_with * RETREAT_FRACTION && advantage < 1.0 {
+                    self.set_army_mode(ArmyMode::Retreating, "losing the fight");
+                } else if self.state.is_under_attack && advantage < ADVANTAGE_RATIO.powi(2) {
+                    self.set_army_mode(ArmyMode::Retreating, "base under attack");
+                } else if supply < MIN_ATTACK_SUPPLY / 2.0 {
+                    self.set_army_mode(ArmyMode::Retreating, "army is gone");
+                } else {
+                    self.refresh_attack_target();
+                }
+            }
+            ArmyMode::Retreating => {
+                let army = self.attack_army();
+                let positions = &self.state.micro.defense.positions;
+                let is_home = army
+                    .iter()
+                    .all(|u| positions.iter().any(|p| u.distance(*p) <= HOME_RADIUS));
+                if is_home || elapsed >= RETREAT_TIMEOUT {
+                    self.set_army_mode(ArmyMode::Defending, "back home");
+                }
+            }
+        }
+    }
+
+    /// Orders the army according to the current mode. Units pulled back to heal aren't part of
+    /// it, so they're left to their micro.
+    pub fn command_army(&self) {
+        let army = self.attack_army();
+        if army.is_empty() {
+            return;
+        }
+        match self.state.attack.mode {
+            ArmyMode::Defending => {
+                if self.state.is_under_attack {
+                    if let Some(base) = self.threatened_base() {
+                        self.a_move(&army.idle(), base, false);
+                    }
+                    return;
+                }
+                let positions = &self.state.micro.defense.positions;
+                for unit in army.idle().iter() {
+                    if let Some(position) = positions.iter().closest(unit.position()) {
+                        if unit.distance(*position) > HOME_RADIUS {
+                            unit.attack(Target::Pos(*position), false);
+                        }
+                    }
+                }
+            }
+            ArmyMode::Gathering { staging } => {
+                for unit in army
+                    .iter()
+                    .filter(|u| u.distance(staging) > GATHER_RADIUS / 2.0 && u.target_pos() != Some(staging))
+                {
+                    unit.attack(Target::Pos(staging), false);
+                }
+            }
+            ArmyMode::Attacking { .. } => {
+                if let Some((target, _)) = self.state.attack.target {
+                    self.a_move(&army.idle(), target, false);
+                }
+            }
+            ArmyMode::Retreating => {
+                let positions = &self.state.micro.defense.positions;
+                for unit in army.iter() {
+                    if let Some(position) = positions.iter().closest(unit.position()) {
+                        if unit.distance(*position) > HOME_RADIUS / 2.0 && unit.target_pos() != Some(*position) {
+                            unit.move_to(Target::Pos(*position), false);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -6,6 +6,9 @@ use float_ord::FloatOrd;
 #[derive(Default, Debug)]
 pub struct RuntimeOptions {
     pub use_tryhard_mining: bool,
+    pub debug_production: bool,
+    /// Game seconds at which we attack if we have an army
+    pub attack_waves: Vec<f32>,
 }
 
 #[bot]
@@ -35,14 +38,55 @@ impl GetBotState for FaxBot {
     }
 }
 
+#[derive(Debug, Copy, Clone)]
+pub struct AbilityCost {
+    pub energy: usize,
+    /// In game seconds
+    pub cooldown: f32,
+}
+
+// You'd expect something like `self.game_data.abilities.get(&ability)?.energy_cost` to work, but
+// apparently the SC2 API doesn't provide ability energy costs or cooldowns anywhere :(
+const ABILITY_COSTS: &[(AbilityId, AbilityCost)] = &[
+    (AbilityId::EffectInjectLarva, AbilityCost { energy: 25, cooldown: 0.0 }),
+    (AbilityId::TransfusionTransfusion, AbilityCost { energy: 50, cooldown: 1.0 }),
+    (AbilityId::BuildCreepTumorQueen, AbilityCost { energy: 25, cooldown: 0.0 }),
+    // Tumors can spread once, a little while after they finish
+    (AbilityId::BuildCreepTumorTumor, AbilityCost { energy: 0, cooldown: 11.0 }),
+];
+
 impl FaxBot {
+    pub fn ability_cost(&self, ability: AbilityId) -> Option<AbilityCost> {
+        ABILITY_COSTS
+            .iter()
+            .find(|(a, _)| *a == ability)
+            .map(|(_, cost)| *cost)
+    }
     pub fn energy_cost(&self, ability: AbilityId) -> Option<usize> {
-        // FIXME: You'd expect something like `Some(self.game_data.abilities.get(&ability)?.energy_cost)`
-        //        to work, but apparently the SC2 API doesn't provide ability energy costs anywhere :(
-        match ability {
-            AbilityId::EffectInjectLarva => Some(25),
-            _ => unimplemented!(),
-        }
+        self.ability_cost(ability).map(|c| c.energy)
+    }
+    /// Whether `unit` has the energy for `ability` and it's off cooldown. Abilities missing
+    /// from the cost table are never castable.
+    pub fn can_cast(&self, unit: &Unit, ability: AbilityId) -> bool {
+        let cost = match self.ability_cost(ability) {
+            Some(cost) => cost,
+            None => return false,
+        };
+        let has_energy = unit.energy().unwrap_or(0) as usize >= cost.energy;
+        let off_cooldown = self
+            .state
+            .micro
+            .ability_used_at
+            .get(&(unit.tag(), ability))
+            .is_none_or(|&t| t + cost.cooldown <= self.time);
+        has_energy && off_cooldown
+    }
+    pub fn cast(&mut self, unit: &Unit, ability: AbilityId, target: Target) {
+        unit.command(ability, target, false);
+        self.state
+            .micro
+            .ability_used_at
+            .insert((unit.tag(), ability), self.time);
     }
     pub fn count_unit(&self, building_id: UnitTypeId) -> usize {
         self.counter().count(building_id) + self.counter().ordered().count(building_id)
@@ -82,9 +126,11 @@ impl Player for FaxBot {
     fn on_step(&mut self, iteration: usize) -> SC2Result<()> {
         self.current_iteration = iteration;
         self.determine_state_for_tick(iteration);
-        if !self.perform_building(iteration)? {
-            self.perform_training(iteration)?;
-        }
+        self.state.production.clear();
+        self.perform_building(iteration)?;
+        self.perform_training(iteration)?;
+        self.execute_production()?;
+        self.plan_gas_workers();
         self.perform_micro(iteration)
     }
     fn on_end(&self, _result: GameResult) -> SC2Result<()> {
@@ -96,15 +142,18 @@ impl Player for FaxBot {
             UnitTypeId::Zergling,
             UnitTypeId::Roach,
             UnitTypeId::Hydralisk,
+            UnitTypeId::Ultralisk,
+            UnitTypeId::Corruptor,
         ];
         match event {
             Event::UnitCreated(tag) => {
                 if let Some(unit) = self._bot.units.my.units.get(tag) {
-                    if army_types.contains(&unit.type_id()) {
-                        let rally = self.get_rally_point();
-                        unit.attack(Target::Pos(rally), false);
-                    }
+                    let unit_type = unit.type_id();
                     self.state.register_unit_created(unit, self.current_iteration);
+                    let is_runby = unit_type == UnitTypeId::Zergling && self.reserve_runby_ling(tag);
+                    if army_types.contains(&unit_type) && !is_runby {
+                        self.state.micro.reinforcements.add(tag, self.current_iteration);
+                    }
                 }
             }
             Event::UnitDestroyed(u, _) => {
@@ -0,0 +1,247 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::production::ProductionKind;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ReservationKey {
+    Expansion,
+    Structure(UnitTypeId),
+    /// Static defense is planned per base, so each location gets its own reservation
+    StaticDefense(UnitTypeId, i32, i32),
+}
+
+impl ReservationKey {
+    pub fn for_structure(unit_type: UnitTypeId, location: Point2) -> ReservationKey {
+        match unit_type {
+            UnitTypeId::SpineCrawler | UnitTypeId::SporeCrawler => {
+                ReservationKey::StaticDefense(unit_type, location.x.round() as i32, location.y.round() as i32)
+            }
+            _ => ReservationKey::Structure(unit_type),
+        }
+    }
+    pub fn for_production(kind: ProductionKind) -> Option<ReservationKey> {
+        match kind {
+            ProductionKind::Expansion => Some(ReservationKey::Expansion),
+            ProductionKind::Structure { unit_type, location, .. } => {
+                Some(ReservationKey::for_structure(unit_type, location))
+            }
+            ProductionKind::Gas => Some(ReservationKey::Structure(UnitTypeId::Extractor)),
+            ProductionKind::Morph { to, .. } => Some(ReservationKey::Structure(to)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Reservation {
+    pub minerals: u32,
+    pub vespene: u32,
+    pub expires_at: usize,
+}
+
+/// Money set aside for planned items, so that larva doesn't spend it before they get placed
+#[derive(Debug, Default)]
+pub struct Budget {
+    pub reservations: HashMap<ReservationKey, Reservation>,
+    // Expired reservations aren't renewed until this iteration, so a plan that keeps failing
+    // can't lock up the money forever
+    suppressed_until: HashMap<ReservationKey, usize>,
+    // Keys reserved since the last `release_unrequested`, i.e. things that are still planned
+    requested: HashSet<ReservationKey>,
+    // Failed builds being retried, which stay reserved until placed again or expired even if the
+    // planner stops asking for them
+    retrying: HashSet<ReservationKey>,
+}
+
+impl Budget {
+    pub fn reserve(
+        &mut self,
+        key: ReservationKey,
+        (minerals, vespene): (u32, u32),
+        iteration: usize,
+        lifetime: usize,
+    ) {
+        self.requested.insert(key);
+        if self.suppressed_until.get(&key).is_some_and(|&until| until > iteration) {
+            return;
+        }
+        self.reservations.entry(key).or_insert(Reservation {
+            minerals,
+            vespene,
+            expires_at: iteration + lifetime,
+        });
+    }
+    /// Reserves money to retry a failed build, replacing any reservation `key` already has
+    pub fn reserve_retry(
+        &mut self,
+        key: ReservationKey,
+        (minerals, vespene): (u32, u32),
+        iteration: usize,
+        lifetime: usize,
+    ) {
+        self.retrying.insert(key);
+        self.reservations.insert(
+            key,
+            Reservation {
+                minerals,
+                vespene,
+                expires_at: iteration + lifetime,
+            },
+        );
+    }
+    pub fn release(&mut self, key: ReservationKey) {
+        self.reservations.remove(&key);
+        self.retrying.remove(&key);
+    }
+    pub fn expire(&mut self, iteration: usize) {
+        let expired = self
+            .reservations
+            .iter()
+            .filter(|(_, r)| r.expires_at <= iteration)
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        for key in expired {
+            println!("{}: Reservation for {:?} expired", iteration, key);
+            self.reservations.remove(&key);
+            self.retrying.remove(&key);
+            self.suppressed_until.insert(key, iteration + 22 * 20);
+        }
+    }
+    /// Drops reservations for anything that wasn't planned again since the last call, so money
+    /// isn't held for something we no longer want
+    pub fn release_unrequested(&mut self, iteration: usize) {
+        let requested = std::mem::take(&mut self.requested);
+        let unrequested = self
+            .reservations
+            .keys()
+            .filter(|k| !requested.contains(k) && !self.retrying.contains(k))
+            .copied()
+            .collect::<Vec<_>>();
+        for key in unrequested {
+            println!("{}: Reservation for {:?} is no longer needed", iteration, key);
+            self.reservations.remove(&key);
+        }
+    }
+    pub fn is_reserved(&self, key: ReservationKey) -> bool {
+        self.reservations.contains_key(&key)
+    }
+    /// Total reserved (minerals, vespene), not counting the reservation held by `key`
+    pub fn reserved_excluding(&self, key: Option<ReservationKey>) -> (u32, u32) {
+        self.reservations
+            .iter()
+            .filter(|(k, _)| Some(**k) != key)
+            .fold((0, 0), |(m, v), (_, r)| (m + r.minerals, v + r.vespene))
+    }
+}
+
+impl FaxBot {
+    /// `can_afford`, but only counting money that hasn't been reserved for something else
+    pub fn can_afford_budgeted(
+        &self,
+        unit_type: UnitTypeId,
+        check_supply: bool,
+        key: Option<ReservationKey>,
+    ) -> bool {
+        let cost = self.get_unit_cost(unit_type);
+        let (reserved_minerals, reserved_vespene) = self.state.budget.reserved_excluding(key);
+        self.can_afford(unit_type, check_supply)
+            && self.minerals >= reserved_minerals + cost.minerals
+            && self.vespene >= reserved_vespene + cost.vespene
+    }
+
+    /// Queues `kind` and reserves its cost until it's placed or the reservation expires
+    pub fn plan_reserved(&mut self, kind: ProductionKind, priority: i32, lifetime: usize) {
+        if let Some(key) = ReservationKey::for_production(kind) {
+            let cost = self.production_cost(kind);
+            self.state
+                .budget
+                .reserve(key, cost, self.current_iteration, lifetime);
+        }
+        self.state.production.push(kind, priority);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::budget::{Budget, ReservationKey};
+    use rust_sc2::prelude::{Point2, UnitTypeId};
+
+    const POOL: ReservationKey = ReservationKey::Structure(UnitTypeId::SpawningPool);
+
+    #[test]
+    fn reserved_excluding_skips_own_key() {
+        let mut budget = Budget::default();
+        budget.reserve(POOL, (200, 0), 0, 100);
+        budget.reserve(ReservationKey::Expansion, (300, 0), 0, 100);
+        assert_eq!(budget.reserved_excluding(None), (500, 0));
+        assert_eq!(budget.reserved_excluding(Some(POOL)), (300, 0));
+        budget.release(ReservationKey::Expansion);
+        assert_eq!(budget.reserved_excluding(None), (200, 0));
+    }
+
+    #[test]
+    fn reserve_keeps_first_expiry() {
+        let mut budget = Budget::default();
+        budget.reserve(POOL, (200, 0), 0, 100);
+        budget.reserve(POOL, (200, 0), 50, 100);
+        assert_eq!(budget.reservations[&POOL].expires_at, 100);
+    }
+
+    #[test]
+    fn expired_reservation_is_suppressed() {
+        let mut budget = Budget::default();
+        budget.reserve(POOL, (200, 0), 0, 100);
+        budget.expire(99);
+        assert!(budget.is_reserved(POOL));
+        budget.expire(100);
+        assert!(!budget.is_reserved(POOL));
+        budget.reserve(POOL, (200, 0), 101, 100);
+        assert!(!budget.is_reserved(POOL));
+        budget.reserve(POOL, (200, 0), 100 + 22 * 20, 100);
+        assert!(budget.is_reserved(POOL));
+    }
+
+    #[test]
+    fn release_unrequested_keeps_planned_keys() {
+        let mut budget = Budget::default();
+        budget.reserve(POOL, (200, 0), 0, 100);
+        budget.reserve(ReservationKey::Expansion, (300, 0), 0, 100);
+        budget.release_unrequested(1);
+        budget.reserve(POOL, (200, 0), 1, 100);
+        budget.release_unrequested(2);
+        assert!(budget.is_reserved(POOL));
+        assert!(!budget.is_reserved(ReservationKey::Expansion));
+    }
+
+    #[test]
+    fn retry_outlives_the_plan() {
+        let mut budget = Budget::default();
+        budget.reserve(POOL, (200, 0), 0, 100);
+        budget.reserve_retry(POOL, (200, 0), 50, 200);
+        assert_eq!(budget.reservations[&POOL].expires_at, 250);
+        budget.release_unrequested(60);
+        budget.release_unrequested(61);
+        assert!(budget.is_reserved(POOL));
+        budget.expire(250);
+        assert!(!budget.is_reserved(POOL));
+        budget.reserve_retry(POOL, (200, 0), 300, 200);
+        budget.release(POOL);
+        budget.reserve(POOL, (200, 0), 301 + 22 * 20, 100);
+        budget.release_unrequested(302 + 22 * 20);
+        budget.release_unrequested(303 + 22 * 20);
+        assert!(!budget.is_reserved(POOL));
+    }
+
+    #[test]
+    fn static_defense_is_keyed_by_location() {
+        let a = ReservationKey::for_structure(UnitTypeId::SporeCrawler, Point2::new(10.2, 20.0));
+        let b = ReservationKey::for_structure(UnitTypeId::SporeCrawler, Point2::new(40.0, 20.0));
+        assert_ne!(a, b);
+        assert_eq!(
+            ReservationKey::for_structure(UnitTypeId::SpawningPool, Point2::new(10.0, 20.0)),
+            POOL
+        );
+    }
+}
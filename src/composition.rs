@@ -0,0 +1,230 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::production::{priority, ProductionKind};
+use float_ord::FloatOrd;
+use rust_sc2::prelude::UnitTypeId as U;
+use std::collections::HashMap;
+
+/// For each enemy unit type, the share of our army supply we'd like to answer it with.
+/// Enemy types that aren't listed here don't influence what we build.
+pub const COUNTER_TABLE: &[(UnitTypeId, &[(UnitTypeId, f32)])] = &[
+    // Protoss
+    (U::Zealot, &[(U::Roach, 0.6), (U::Ultralisk, 0.4)]),
+    (U::Adept, &[(U::Roach, 1.0)]),
+    (U::Stalker, &[(U::Hydralisk, 0.4), (U::Roach, 0.3), (U::Zergling, 0.3)]),
+    (U::Immortal, &[(U::Zergling, 0.5), (U::Hydralisk, 0.5)]),
+    (U::Archon, &[(U::Hydralisk, 0.7), (U::Roach, 0.3)]),
+    (U::Colossus, &[(U::Corruptor, 0.5), (U::Roach, 0.5)]),
+    (U::Phoenix, &[(U::Hydralisk, 1.0)]),
+    (U::Oracle, &[(U::Hydralisk, 1.0)]),
+    (U::VoidRay, &[(U::Hydralisk, 1.0)]),
+    (U::Tempest, &[(U::Corruptor, 0.5), (U::Hydralisk, 0.5)]),
+    (U::Carrier, &[(U::Corruptor, 0.6), (U::Hydralisk, 0.4)]),
+    // Terran
+    (U::Marine, &[(U::Roach, 0.3), (U::LurkerMP, 0.3), (U::Ultralisk, 0.2), (U::Hydralisk, 0.2)]),
+    (U::Marauder, &[(U::Hydralisk, 0.4), (U::LurkerMP, 0.3), (U::Roach, 0.3)]),
+    (U::Hellion, &[(U::Roach, 1.0)]),
+    (U::HellionTank, &[(U::Roach, 1.0)]),
+    (U::SiegeTank, &[(U::BroodLord, 0.4), (U::Zergling, 0.3), (U::Roach, 0.3)]),
+    (U::SiegeTankSieged, &[(U::BroodLord, 0.4), (U::Zergling, 0.3), (U::Roach, 0.3)]),
+    (U::Thor, &[(U::Zergling, 0.5), (U::Hydralisk, 0.5)]),
+    (U::Medivac, &[(U::Hydralisk, 1.0)]),
+    (U::VikingFighter, &[(U::Hydralisk, 1.0)]),
+    (U::Banshee, &[(U::Hydralisk, 1.0)]),
+    (U::LiberatorAG, &[(U::Hydralisk, 1.0)]),
+    (U::Liberator, &[(U::Hydralisk, 1.0)]),
+    (U::Battlecruiser, &[(U::Corruptor, 0.6), (U::Hydralisk, 0.4)]),
+    // Zerg
+    (U::Zergling, &[(U::Roach, 1.0)]),
+    (U::Baneling, &[(U::Roach, 0.6), (U::Ultralisk, 0.4)]),
+    (U::Roach, &[(U::Roach, 0.6), (U::Hydralisk, 0.4)]),
+    (U::Hydralisk, &[(U::Roach, 0.4), (U::LurkerMP, 0.3), (U::Zergling, 0.3)]),
+    (U::Mutalisk, &[(U::Hydralisk, 1.0)]),
+    (U::Corruptor, &[(U::Hydralisk, 1.0)]),
+    (U::BroodLord, &[(U::Corruptor, 0.7), (U::Hydralisk, 0.3)]),
+    (U::Ultralisk, &[(U::Hydralisk, 0.5), (U::Roach, 0.5)]),
+];
+
+/// What we build when we haven't seen anything worth countering
+const DEFAULT_COMPOSITION: &[(UnitTypeId, f32)] = &[(U::Roach, 0.6), (U::Hydralisk, 0.4)];
+
+/// Army units we know how to make, the tech they need, and whether they come from larva
+const ARMY_UNITS: &[(UnitTypeId, UnitTypeId, bool)] = &[
+    (U::Zergling, U::SpawningPool, true),
+    (U::Roach, U::RoachWarren, true),
+    (U::Hydralisk, U::HydraliskDen, true),
+    (U::Corruptor, U::Spire, true),
+    (U::LurkerMP, U::LurkerDenMP, false),
+    (U::Ultralisk, U::UltraliskCavern, true),
+    (U::BroodLord, U::GreaterSpire, false),
+];
+
+/// Army units morphed from other army units: from, to, and the morph ability
+const ARMY_MORPHS: &[(UnitTypeId, UnitTypeId, AbilityId)] = &[
+    (U::Hydralisk, U::LurkerMP, AbilityId::MorphLurker),
+    (U::Corruptor, U::BroodLord, AbilityId::MorphToBroodLordBroodLord),
+];
+
+/// Share of army supply for each of our unit types that answers the given enemy supply per type
+pub fn counter_composition(enemy_supply: &HashMap<UnitTypeId, f32>) -> HashMap<UnitTypeId, f32> {
+    let mut desired = HashMap::new();
+    let mut total = 0.0;
+    for (enemy_type, counters) in COUNTER_TABLE {
+        if let Some(supply) = enemy_supply.get(enemy_type) {
+            for (unit_type, share) in counters.iter() {
+                *desired.entry(*unit_type).or_insert(0.0) += supply * share;
+            }
+            total += supply;
+        }
+    }
+    if total <= 0.0 {
+        return DEFAULT_COMPOSITION.iter().copied().collect();
+    }
+    for share in desired.values_mut() {
+        *share /= total;
+    }
+    desired
+}
+
+impl FaxBot {
+    /// Desired share of army supply for each of our unit types, based on what we've seen recently
+    pub fn desired_army_composition(&self) -> HashMap<UnitTypeId, f32> {
+        let mut enemy_supply = HashMap::new();
+        for (_, unit_type) in self.state.get_recent_enemy_spotted_information(self.current_iteration) {
+            *enemy_supply.entry(unit_type).or_insert(0.0) += self.get_unit_cost(unit_type).supply;
+        }
+        counter_composition(&enemy_supply)
+    }
+
+    fn is_army_unit_available(&self, unit_type: UnitTypeId) -> bool {
+        ARMY_UNITS
+            .iter()
+            .any(|&(t, tech, _)| t == unit_type && self.has_tech(tech))
+    }
+
+    /// Available army units sorted by how far below their desired share of the army they are.
+    /// `planned` holds units queued this step that don't show up in the counter yet.
+    fn army_unit_deficits(&self, planned: &HashMap<UnitTypeId, usize>) -> Vec<(UnitTypeId, f32)> {
+        let desired = self.desired_army_composition();
+        let supply_of = |unit_type| {
+            let count = self.count_unit(unit_type) + planned.get(&unit_type).copied().unwrap_or(0);
+            count as f32 * self.get_unit_cost(unit_type).supply
+        };
+        let total = ARMY_UNITS.iter().map(|&(t, _, _)| supply_of(t)).sum::<f32>().max(1.0);
+        let mut deficits = ARMY_UNITS
+            .iter()
+            .filter(|&&(t, _, _)| self.is_army_unit_available(t))
+            .map(|&(t, _, _)| {
+                let wanted = desired.get(&t).copied().unwrap_or(0.0);
+                (t, wanted - supply_of(t) / total)
+            })
+            .collect::<Vec<_>>();
+        // Morphed units only come from their precursor, so what's missing of them is made as that
+        for &(from, to, _) in ARMY_MORPHS {
+            let missing = deficits.iter().find(|&&(t, _)| t == to).map_or(0.0, |&(_, d)| d.max(0.0));
+            if let Some(precursor) = deficits.iter_mut().find(|(t, _)| *t == from) {
+                precursor.1 += missing;
+            }
+        }
+        deficits.sort_by_key(|&(_, d)| std::cmp::Reverse(FloatOrd(d)));
+        deficits
+    }
+
+    /// Picks the next army unit to make from larva
+    pub fn choose_army_unit(
+        &self,
+        planned: &HashMap<UnitTypeId, usize>,
+        is_mineral_starved: bool,
+        is_gas_starved: bool,
+    ) -> Option<UnitTypeId> {
+        let is_larva_unit = |unit_type| ARMY_UNITS.iter().any(|&(t, _, larva)| t == unit_type && larva);
+        let candidates = self
+            .army_unit_deficits(planned)
+            .into_iter()
+            .filter(|&(t, _)| is_larva_unit(t))
+            .collect::<Vec<_>>();
+        let has_gas_units = candidates.iter().any(|&(t, _)| t != UnitTypeId::Zergling);
+        if is_gas_starved || (!has_gas_units && self.state.is_under_attack) {
+            return candidates
+                .iter()
+                .any(|&(t, _)| t == UnitTypeId::Zergling)
+                .then_some(UnitTypeId::Zergling);
+        }
+        if is_mineral_starved {
+            return candidates
+                .iter()
+                .max_by_key(|&&(t, _)| self.get_unit_cost(t).vespene)
+                .map(|&(t, _)| t);
+        }
+        candidates
+            .into_iter()
+            .find(|&(t, _)| t != UnitTypeId::Zergling || has_gas_units)
+            .map(|(t, _)| t)
+    }
+
+    /// Queues morphs of existing army units, i.e. hydras into lurkers
+    pub fn plan_army_morphs(&mut self) {
+        let deficits = self.army_unit_deficits(&HashMap::new());
+        for &(from, to, ability) in ARMY_MORPHS {
+            let wanted = deficits.iter().any(|&(t, d)| t == to && d > 0.0);
+            if wanted && self.counter().count(from) > 0 {
+                self.state
+                    .production
+                    .push(ProductionKind::Morph { from, to, ability }, priority::ARMY);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::composition::{counter_composition, COUNTER_TABLE, DEFAULT_COMPOSITION};
+    use rust_sc2::prelude::UnitTypeId as U;
+    use std::collections::HashMap;
+
+    fn assert_close(actual: Option<&f32>, expected: f32) {
+        let actual = *actual.unwrap();
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn default_without_enemies() {
+        let desired = counter_composition(&HashMap::new());
+        assert_eq!(desired, DEFAULT_COMPOSITION.iter().copied().collect());
+    }
+
+    #[test]
+    fn unlisted_enemies_are_ignored() {
+        let enemy_supply = vec![(U::Probe, 12.0)].into_iter().collect();
+        let desired = counter_composition(&enemy_supply);
+        assert_eq!(desired, DEFAULT_COMPOSITION.iter().copied().collect());
+    }
+
+    #[test]
+    fn single_enemy_type_uses_its_counters() {
+        let enemy_supply = vec![(U::Zealot, 8.0), (U::Probe, 20.0)].into_iter().collect();
+        let desired = counter_composition(&enemy_supply);
+        assert_eq!(desired.len(), 2);
+        assert_close(desired.get(&U::Roach), 0.6);
+        assert_close(desired.get(&U::Ultralisk), 0.4);
+    }
+
+    #[test]
+    fn counters_are_weighted_by_enemy_supply() {
+        // 6 supply of zealots and 2 of phoenixes
+        let enemy_supply = vec![(U::Zealot, 6.0), (U::Phoenix, 2.0)].into_iter().collect();
+        let desired = counter_composition(&enemy_supply);
+        assert_close(desired.get(&U::Roach), 0.6 * 0.75);
+        assert_close(desired.get(&U::Ultralisk), 0.4 * 0.75);
+        assert_close(desired.get(&U::Hydralisk), 0.25);
+    }
+
+    #[test]
+    fn counter_shares_sum_to_one() {
+        for (enemy_type, counters) in COUNTER_TABLE {
+            let total = counters.iter().map(|(_, share)| share).sum::<f32>();
+            assert!((total - 1.0).abs() < 1e-5, "{:?} counters sum to {}", enemy_type, total);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::budget::ReservationKey;
+
+/// Builds that keep failing at the same spot are given up on after this many tries
+pub const MAX_ATTEMPTS: usize = 3;
+/// Bot steps, not game loops, a drone gets to show its build order. The order only shows up on
+/// the drone a step after it was given, however many game loops a step is.
+const ORDER_GRACE_STEPS: usize = 5;
+/// How long a drone gets to walk to the spot and start the structure
+const CONSTRUCTION_TIMEOUT: usize = 22 * 60;
+/// How long failures count against a spot or a drone
+const FAILURE_MEMORY: usize = 22 * 60;
+const RETRY_RESERVATION_LIFETIME: usize = 22 * 30;
+
+/// A drone that's been told to build something, from the order until the structure shows up
+#[derive(Debug, Copy, Clone)]
+pub struct ConstructionJob {
+    pub unit_type: UnitTypeId,
+    /// Where production asked for the structure
+    pub requested: Point2,
+    /// Where we actually placed it
+    pub location: Point2,
+    pub drone: u64,
+    pub key: ReservationKey,
+    pub ordered_at: usize,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ConstructionFailure {
+    unit_type: UnitTypeId,
+    requested: Point2,
+    drone: u64,
+    failed_at: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Construction {
+    pub jobs: Vec<ConstructionJob>,
+    failures: Vec<ConstructionFailure>,
+}
+
+impl Construction {
+    pub fn start(&mut self, job: ConstructionJob) {
+        self.jobs.retain(|j| j.drone != job.drone);
+        self.jobs.push(job);
+    }
+    pub fn finish(&mut self, drone: u64) {
+        self.jobs.retain(|j| j.drone != drone);
+    }
+    /// Records a failed job, and returns how often building there has failed recently
+    pub fn fail(&mut self, job: ConstructionJob, iteration: usize) -> usize {
+        self.finish(job.drone);
+        self.failures.retain(|f| f.failed_at + FAILURE_MEMORY > iteration);
+        self.failures.push(ConstructionFailure {
+            unit_type: job.unit_type,
+            requested: job.requested,
+            drone: job.drone,
+            failed_at: iteration,
+        });
+        self.recent_failures(job.unit_type, job.requested, iteration)
+    }
+    pub fn recent_failures(&self, unit_type: UnitTypeId, requested: Point2, iteration: usize) -> usize {
+        self.failures
+            .iter()
+            .filter(|f| {
+                f.unit_type == unit_type
+                    && f.requested.distance(requested) < 1.0
+                    && f.failed_at + FAILURE_MEMORY > iteration
+            })
+            .count()
+    }
+    /// Whether `drone` recently failed to build a `unit_type`, so another drone should try
+    pub fn has_failed(&self, drone: u64, unit_type: UnitTypeId, iteration: usize) -> bool {
+        self.failures
+            .iter()
+            .any(|f| f.drone == drone && f.unit_type == unit_type && f.failed_at + FAILURE_MEMORY > iteration)
+    }
+}
+
+/// An expansion a drone has been sent to build a hatchery at
+#[derive(Debug, Copy, Clone)]
+pub struct ExpansionClaim {
+    pub location: Point2,
+    pub drone: u64,
+}
+
+/// Expansions that already have a drone on the way, so we don't send a second one
+#[derive(Debug, Default)]
+pub struct ExpansionClaims {
+    pub claims: Vec<ExpansionClaim>,
+}
+
+impl ExpansionClaims {
+    pub fn claim(&mut self, location: Point2, drone: u64) {
+        self.claims.push(ExpansionClaim { location, drone });
+    }
+    pub fn is_claimed(&self, location: Point2) -> bool {
+        self.claims.iter().any(|c| c.location.distance(location) < 3.0)
+    }
+    pub fn release(&mut self, location: Point2) {
+        self.claims.retain(|c| c.location.distance(location) >= 3.0);
+    }
+    /// Drops the claim held by `drone`, if any
+    pub fn release_drone(&mut self, drone: u64) -> Option<ExpansionClaim> {
+        let index = self.claims.iter().position(|c| c.drone == drone)?;
+        Some(self.claims.remove(index))
+    }
+}
+
+impl FaxBot {
+    /// Drops claims once the hatchery is started. Claims of failed builds are dropped by
+    /// `update_construction_jobs`.
+    pub fn update_expansion_claims(&mut self) {
+        for claim in self.state.expansion_claims.claims.clone() {
+            if self.units.my.townhalls.iter().any(|th| th.distance(claim.location) < 3.0) {
+                self.state.expansion_claims.release(claim.location);
+            }
+        }
+    }
+
+    /// Finishes jobs whose structure has appeared, and fails the ones whose drone died, dropped
+    /// the order (e.g. the spot was blocked or lost creep) or is taking too long. Failed jobs keep
+    /// their money reserved, whether or not they're still planned, so production can retry them
+    /// with another drone or spot until the retry reservation expires.
+    pub fn update_construction_jobs(&mut self) {
+        let iteration = self.current_iteration;
+        for job in self.state.construction.jobs.clone() {
+            let is_started = self
+                .units
+                .my
+                .structures
+                .iter()
+                .any(|s| s.type_id() == job.unit_type && s.distance(job.location) < 2.0);
+            if is_started {
+                self.state.construction.finish(job.drone);
+                continue;
+            }
+            let build_ability = self.game_data.units.get(&job.unit_type).and_then(|d| d.ability);
+            let drone = self.units.my.workers.get(job.drone);
+            let is_building = drone.is_some_and(|w| w.orders().iter().any(|o| Some(o.ability) == build_ability));
+            let reason = if !self.state.micro.workers.registry.contains(job.drone) {
+                "lost its drone"
+            } else if job.ordered_at + ORDER_GRACE_STEPS < iteration && !is_building {
+                "was never placed"
+            } else if job.ordered_at + CONSTRUCTION_TIMEOUT < iteration {
+                "timed out"
+            } else {
+                continue;
+            };
+            if let Some(drone) = drone {
+                drone.stop(false);
+            }
+            self.state.micro.workers.release(job.drone);
+            self.state.expansion_claims.release_drone(job.drone);
+            let failures = self.state.construction.fail(job, iteration);
+            if failures >= MAX_ATTEMPTS {
+                println!(
+                    "{}: {:?} at {:?} {}, giving up after {} attempts",
+                    iteration, job.unit_type, job.location, reason, failures
+                );
+                self.state.budget.release(job.key);
+            } else {
+                println!("{}: {:?} at {:?} {}, retrying", iteration, job.unit_type, job.location, reason);
+                let cost = self.get_unit_cost(job.unit_type);
+                self.state.budget.reserve_retry(
+                    job.key,
+                    (cost.minerals, cost.vespene),
+                    iteration,
+                    RETRY_RESERVATION_LIFETIME,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::production::{priority, ProductionKind};
+
+const SPINES_PER_THREATENED_BASE: usize = 2;
+/// Spines go at the base's entrance, close enough that drones and queens can help hold it
+const SPINE_MIN_DISTANCE: f32 = 5.0;
+const SPINE_MAX_DISTANCE: f32 = 12.0;
+const STATIC_DEFENSE_RESERVATION_LIFETIME: usize = 22 * 20;
+
+impl FaxBot {
+    /// The base closest to whatever is attacking us, or the one closest to the enemy otherwise
+    pub fn threatened_base(&self) -> Option<Point2> {
+        let attackers = self
+            .state
+            .get_recent_enemy_spotted_information(self.current_iteration)
+            .into_iter()
+            .filter(|(_, t)| !t.is_worker())
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>();
+        if self.state.is_under_attack {
+            let closest = self
+                .state
+                .bases
+                .iter()
+                .filter_map(|b| Some((*b, attackers.iter().closest_distance(*b)?)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            if let Some((base, _)) = closest {
+                return Some(base);
+            }
+        }
+        self.state.bases.iter().closest(self.enemy_start).copied()
+    }
+
+    /// Counts finished and ordered structures of `unit_type` within `radius` of `position`
+    pub fn count_structures_near(&self, unit_type: UnitTypeId, position: Point2, radius: f32) -> usize {
+        let existing = self
+            .units
+            .my
+            .structures
+            .filter(|u| u.type_id() == unit_type && u.distance(position) <= radius)
+            .len();
+        let build_ability = self.game_data.units.get(&unit_type).and_then(|d| d.ability);
+        let ordered = self
+            .units
+            .my
+            .workers
+            .iter()
+            .filter(|w| {
+                w.orders().iter().any(|o| {
+                    Some(o.ability) == build_ability
+                        && matches!(o.target, Target::Pos(p) if p.distance(position) <= radius)
+                })
+            })
+            .count();
+        existing + ordered
+    }
+
+    /// Where a spore covering the mineral line of the given townhall should go
+    pub fn mineral_line_defense_position(&self, townhall: Point2) -> Point2 {
+        townhall - self.vec_away_from_resources(townhall) * 3.5
+    }
+
+    fn plan_defense_structure(&mut self, unit_type: UnitTypeId, location: Point2) {
+        self.plan_reserved(
+            ProductionKind::Structure {
+                unit_type,
+                location,
+                exact: false,
+            },
+            priority::STATIC_DEFENSE,
+            STATIC_DEFENSE_RESERVATION_LIFETIME,
+        );
+    }
+
+    /// Spines in front of the threatened base against early aggression, and a spore in every
+    /// mineral line once we know air or cloaked units are coming
+    pub fn plan_static_defense(&mut self) {
+        let has_pool = !self
+            .units
+            .my
+            .structures
+            .filter(|u| u.type_id() == UnitTypeId::SpawningPool && u.is_ready())
+            .is_empty();
+        if !has_pool {
+            return;
+        }
+        if self.state.threats.early_aggression && self.time < 60.0 * 8.0 {
+            if let Some(base) = self.threatened_base() {
+                let location = self.base_entrance(base, SPINE_MIN_DISTANCE, SPINE_MAX_DISTANCE);
+                if self.count_structures_near(UnitTypeId::SpineCrawler, location, 6.0) < SPINES_PER_THREATENED_BASE {
+                    self.plan_defense_structure(UnitTypeId::SpineCrawler, location);
+                }
+            }
+        }
+        if self.state.threats.wants_spores() {
+            let townhalls = self
+                .units
+                .my
+                .townhalls
+                .ready()
+                .iter()
+                .map(|th| th.position())
+                .collect::<Vec<_>>();
+            for townhall in townhalls {
+                let location = self.mineral_line_defense_position(townhall);
+                if self.count_structures_near(UnitTypeId::SporeCrawler, location, 5.0) < 1 {
+                    self.plan_defense_structure(UnitTypeId::SporeCrawler, location);
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,35 @@
 use rust_sc2::prelude::*;
 
 use crate::bot::FaxBot;
+use crate::budget::ReservationKey;
+use crate::construction::{ConstructionJob, MAX_ATTEMPTS};
+use crate::production::{priority, ProductionKind, ProductionStatus};
+use crate::research::is_researcher;
+use std::collections::HashMap;
+
+/// How long a planned structure may hold on to its money before it has to be placed
+const RESERVATION_LIFETIME: usize = 22 * 45;
+/// Banked gas above which half of the gas drones come off gas, then the other half
+const GAS_BANK_PULL: [u32; 2] = [500, 1000];
+/// Banked gas below which they go back on. Lower than `GAS_BANK_PULL` so drones don't flip
+/// between gas and minerals while the bank hovers around a threshold.
+const GAS_BANK_RETURN: [u32; 2] = [300, 700];
+
+/// How many halves of the gas drones should be off gas with `banked` gas, given how many are now
+pub fn gas_halves_pulled(pulled: usize, banked: u32) -> usize {
+    let mut pulled = pulled;
+    while pulled < GAS_BANK_PULL.len() && banked > GAS_BANK_PULL[pulled] {
+        pulled += 1;
+    }
+    while pulled > 0 && banked < GAS_BANK_RETURN[pulled - 1] {
+        pulled -= 1;
+    }
+    pulled
+}
 
 impl FaxBot {
     /// Returns whether or not gasses were taken this iteration
-    fn ensure_taken_gasses(&mut self, num_gasses: usize) -> bool {
+    pub fn ensure_taken_gasses(&mut self, num_gasses: usize) -> bool {
         if self.count_unit(UnitTypeId::Extractor) >= self.state.desired_gasses {
             return false;
         }
@@ -20,13 +45,23 @@ impl FaxBot {
             })
             .len()
             + self.counter().ordered().count(UnitTypeId::Extractor);
-        if self.can_afford(UnitTypeId::Extractor, false) && current_gasses < num_gasses {
-            if let Some(w) = available_workers.pop() {
+        let key = ReservationKey::Structure(UnitTypeId::Extractor);
+        if self.can_afford_budgeted(UnitTypeId::Extractor, false, Some(key)) && current_gasses < num_gasses {
+            if let Some(w) = available_workers.pop().cloned() {
                 for base in self.state.bases.iter() {
                     if let Some(nearest_free_gas) = self.find_gas_placement(*base) {
                         w.build_gas(nearest_free_gas.tag(), false);
-                        self.state.micro.drones.insert(w.tag(), crate::micro::DroneTask::Construct { queued_at: self.current_iteration });
+                        self.state.micro.workers.reserve_for_building(w.tag(), self.current_iteration);
+                        self.state.construction.start(ConstructionJob {
+                            unit_type: UnitTypeId::Extractor,
+                            requested: nearest_free_gas.position(),
+                            location: nearest_free_gas.position(),
+                            drone: w.tag(),
+                            key,
+                            ordered_at: self.current_iteration,
+                        });
                         self.subtract_resources(UnitTypeId::Extractor, false);
+                        self.state.budget.release(key);
                         return true;
                     }
                 }
@@ -35,7 +70,7 @@ impl FaxBot {
         false
     }
 
-    fn least_busy_hatch(&self) -> Option<Unit> {
+    pub fn least_busy_hatch(&self) -> Option<Unit> {
         self.units
             .my
             .townhalls
@@ -56,76 +91,95 @@ impl FaxBot {
             + (supply_per_provider.floor() as usize) * self.counter().ordered().count(supply_unit)
     }
 
-    fn determine_best_expansion_order(&self) -> Vec<Point2> {
+    pub fn determine_best_expansion_order(&self) -> Vec<Point2> {
         let should_use_low_gas_bases = self.state.desired_bases > 2;
         let mut expansions: Vec<_> = self
             .expansions
             .iter()
             .filter(|e| e.base.is_none() && (should_use_low_gas_bases || e.geysers.len() >= 2))
+            .filter(|e| !self.state.expansion_claims.is_claimed(e.loc))
             .map(|e| e.loc)
             .collect();
         expansions.sort_by_key(|e| float_ord::FloatOrd(e.distance(self.start_location)));
         expansions
     }
 
-    fn take_expansion(&mut self, position: Point2) -> bool {
-        // FIXME: This may queue multiple hatcheries in the same expansion (:
-        if self.can_afford(UnitTypeId::Hatchery, false) {
-            println!("Expanding");
-            self.create_building(UnitTypeId::Hatchery, position, true)
-        } else {
-            false
+    pub fn take_expansion(&mut self, position: Point2) -> bool {
+        if !self.can_afford_budgeted(UnitTypeId::Hatchery, false, Some(ReservationKey::Expansion)) {
+            return false;
+        }
+        match self.create_reserved_building(UnitTypeId::Hatchery, position, true, ReservationKey::Expansion) {
+            Some(drone) => {
+                println!("{}: Expanding to {:?} with drone {}", self.current_iteration, position, drone);
+                self.state.expansion_claims.claim(position, drone);
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn get_rally_point(&self) -> Point2 {
-        if let Some(pos) = self.state.bases.iter().closest(self.enemy_start) {
-            pos.towards(self.enemy_start, 7.0)
-        } else {
-            self.start_location
-        }
+    fn wants_upgrade(&self, researcher: UnitTypeId, upgrade: UpgradeId) -> bool {
+        self.units
+            .my
+            .all
+            .filter(|unit| is_researcher(researcher, unit.type_id()) && unit.is_ready())
+            .len()
+            > 0
+            && !self.has_upgrade(upgrade)
+            && !self.is_ordered_upgrade(upgrade)
     }
 
+    /// Returns whether the research was started
     pub fn research_upgrade(
         &mut self,
         researcher: UnitTypeId,
         upgrade: UpgradeId,
         ability: AbilityId,
     ) -> bool {
+        let cost = self.get_upgrade_cost(upgrade);
+        let (reserved_minerals, reserved_vespene) = self.state.budget.reserved_excluding(None);
+        if !self.wants_upgrade(researcher, upgrade)
+            || !self.can_afford_upgrade(upgrade)
+            || self.minerals < reserved_minerals + cost.minerals
+            || self.vespene < reserved_vespene + cost.vespene
+        {
+            return false;
+        }
         let researchers = self
             .units
             .my
             .all
-            .filter(|unit| unit.type_id() == researcher && unit.is_ready() && unit.orders().len() < 5);
-        if researchers.len() > 0
-            && !self.has_upgrade(upgrade)
-            && !self.is_ordered_upgrade(upgrade)
-        {
-            if let Some(candidate) = researchers.min(|unit| unit.orders().len()) {
-                if self.can_afford_upgrade(upgrade) {
-                    candidate.use_ability(ability, true);
-                }
-            }
+            .filter(|unit| is_researcher(researcher, unit.type_id()) && unit.is_ready() && unit.orders().len() < 5);
+        if let Some(candidate) = researchers.min(|unit| unit.orders().len()) {
+            candidate.use_ability(ability, true);
+            self.subtract_upgrade_cost(upgrade);
             true
         } else {
             false
         }
     }
 
-    fn should_expand(&self) -> bool {
+    fn wants_more_bases(&self) -> bool {
         let num_hatcheries = self.units.my.townhalls.len() + self.counter().ordered().count(UnitTypeId::Hatchery);
         let desired_bases = if self.runtime_options.use_tryhard_mining && self.supply_used > 38 {
             self.state.desired_bases.max(3)
         } else {
             self.state.desired_bases
         };
-        self.supply_used >= self.state.build_order.first_hatch_supply
-            && num_hatcheries < desired_bases
-            && !self.state.is_under_attack
+        num_hatcheries < desired_bases && !self.state.is_under_attack
+    }
+
+    fn should_expand(&self) -> bool {
+        self.supply_used >= self.state.build_order.first_hatch_supply && self.wants_more_bases()
     }
 
-    pub fn perform_building(&mut self, _iteration: usize) -> SC2Result<bool> {
-        let mut did_attempt_build = true;
+    /// Start saving for the next hatchery a little before we actually want to place it, so the
+    /// drone can walk there while the money comes in
+    fn should_save_for_expansion(&self) -> bool {
+        self.supply_used + 2 >= self.state.build_order.first_hatch_supply && self.wants_more_bases()
+    }
+
+    pub fn perform_building(&mut self, _iteration: usize) -> SC2Result<()> {
         // FIXME: This is ugly
         if self.state.desired_bases > 2 {
             self.state.desired_gasses = 8;
@@ -138,77 +192,180 @@ impl FaxBot {
         if (self.supply_used >= self.state.build_order.spawning_pool_supply || self.state.is_under_attack)
             && self.count_unit(UnitTypeId::SpawningPool) < 1
         {
-            self.create_building(UnitTypeId::SpawningPool, main_build_location, false);
-        } else if self.should_expand() {
-            for expansion in self.determine_best_expansion_order() {
-                if self.take_expansion(expansion) {
-                    break;
-                }
-            }
-        } else if self.supply_used >= 17 && self.ensure_taken_gasses(self.state.desired_gasses) {
-            // Nothing to do here, ensure_taken_gasses does everything as a side effect
-        } else if self.supply_used >= 32 && self.count_unit(UnitTypeId::RoachWarren) < 1 {
-            self.create_building(UnitTypeId::RoachWarren, main_build_location, false);
-        } else if self.units.my.townhalls.len() > 2 && self.state.desired_bases > 2 && self.count_unit(UnitTypeId::Lair) < 1 {
-            if let Some(hatch) = self
-                .units
-                .my
-                .townhalls
-                .filter(|hatch| hatch.is_ready() && hatch.orders().is_empty())
-                .first()
-            {
-                hatch.use_ability(AbilityId::UpgradeToLairLair, false);
-            } else {
-                did_attempt_build = false;
-            }
-        } else if self.counter().count(UnitTypeId::Lair) > 0
+            self.plan_reserved(
+                ProductionKind::Structure {
+                    unit_type: UnitTypeId::SpawningPool,
+                    location: main_build_location,
+                    exact: false,
+                },
+                priority::SPAWNING_POOL,
+                RESERVATION_LIFETIME,
+            );
+        }
+        if self.should_expand() {
+            self.plan_reserved(ProductionKind::Expansion, priority::EXPANSION, RESERVATION_LIFETIME);
+        } else if self.should_save_for_expansion() {
+            let cost = self.production_cost(ProductionKind::Expansion);
+            self.state.budget.reserve(
+                ReservationKey::Expansion,
+                cost,
+                self.current_iteration,
+                RESERVATION_LIFETIME,
+            );
+        }
+        if self.supply_used >= 17 && self.count_unit(UnitTypeId::Extractor) < self.state.desired_gasses {
+            self.state.production.push(ProductionKind::Gas, priority::GAS);
+        }
+        if self.supply_used >= 32 && self.count_unit(UnitTypeId::RoachWarren) < 1 {
+            self.plan_reserved(
+                ProductionKind::Structure {
+                    unit_type: UnitTypeId::RoachWarren,
+                    location: main_build_location,
+                    exact: false,
+                },
+                priority::TECH_STRUCTURE,
+                RESERVATION_LIFETIME,
+            );
+        }
+        if self.units.my.townhalls.len() > 2 && self.state.desired_bases > 2
+            && !self.has_tech(UnitTypeId::Lair)
+            && self.count_unit(UnitTypeId::Lair) < 1
+        {
+            self.plan_reserved(
+                ProductionKind::Morph {
+                    from: UnitTypeId::Hatchery,
+                    to: UnitTypeId::Lair,
+                    ability: AbilityId::UpgradeToLairLair,
+                },
+                priority::TECH_MORPH,
+                RESERVATION_LIFETIME,
+            );
+        }
+        if self.counter().count(UnitTypeId::Lair) > 0
             && self.count_unit(UnitTypeId::HydraliskDen) < 1
         {
-            // println!("{}: Want Hydra Den", _iteration);
-            self.create_building(UnitTypeId::HydraliskDen, main_build_location, false);
-        } else if (self.counter().count(UnitTypeId::Lair) > 0 && self.research_upgrade(
-            UnitTypeId::RoachWarren,
-            UpgradeId::GlialReconstitution,
-            AbilityId::ResearchGlialRegeneration,
-        )) || self.research_upgrade(
-            UnitTypeId::HydraliskDen,
-            UpgradeId::EvolveGroovedSpines,
-            AbilityId::ResearchGroovedSpines) ||
-            self.research_upgrade(
-                UnitTypeId::HydraliskDen,
-                UpgradeId::EvolveMuscularAugments,
-                AbilityId::ResearchMuscularAugments,
-            ) {
-            did_attempt_build = true;
-        } else {
-            did_attempt_build = false;
+            self.plan_reserved(
+                ProductionKind::Structure {
+                    unit_type: UnitTypeId::HydraliskDen,
+                    location: main_build_location,
+                    exact: false,
+                },
+                priority::TECH_STRUCTURE,
+                RESERVATION_LIFETIME,
+            );
+        }
+        if self.count_unit(UnitTypeId::EvolutionChamber) < self.desired_evolution_chambers() {
+            self.plan_reserved(
+                ProductionKind::Structure {
+                    unit_type: UnitTypeId::EvolutionChamber,
+                    location: main_build_location,
+                    exact: false,
+                },
+                priority::TECH_STRUCTURE,
+                RESERVATION_LIFETIME,
+            );
         }
-        Ok(did_attempt_build)
+        self.plan_late_game_tech(main_build_location);
+        self.plan_static_defense();
+        self.plan_research();
+        Ok(())
+    }
+
+    pub fn create_building(&mut self, unit_type: UnitTypeId, location: Point2, exact: bool) -> bool {
+        self.create_reserved_building(unit_type, location, exact, ReservationKey::for_structure(unit_type, location))
+            .is_some()
     }
 
-    fn create_building(&mut self, unit_type: UnitTypeId, location: Point2, exact: bool) -> bool {
+    fn create_reserved_building(
+        &mut self,
+        unit_type: UnitTypeId,
+        location: Point2,
+        exact: bool,
+        key: ReservationKey,
+    ) -> Option<u64> {
+        let iteration = self.current_iteration;
+        let failures = self.state.construction.recent_failures(unit_type, location, iteration);
+        if failures >= MAX_ATTEMPTS {
+            return None;
+        }
         let mut options = PlacementOptions::default();
         if exact {
             options.max_distance = 0;
+        } else if failures > 0 {
+            // Whatever blocked the last spot is probably still there
+            options.random = true;
         }
-        if !self.can_afford(unit_type, false) {
-            return false;
+        if !self.can_afford_budgeted(unit_type, false, Some(key)) {
+            return None;
         }
         if let Some(w) = self
             .units
             .my
             .workers
-            .filter(|u| !u.orders().iter().any(|o| o.ability.is_constructing()))
+            .filter(|u| {
+                !u.orders().iter().any(|o| o.ability.is_constructing())
+                    && !self.state.construction.has_failed(u.tag(), unit_type, iteration)
+            })
             .first()
         {
-            if let Some(location) = self.find_placement(unit_type, location, options) {
-                self.state.micro.drones.insert(w.tag(), crate::micro::DroneTask::Construct { queued_at: self.current_iteration });
-                w.build(unit_type, location, false);
+            if let Some(placement) = self.find_placement(unit_type, location, options) {
+                self.state.micro.workers.reserve_for_building(w.tag(), iteration);
+                self.state.construction.start(ConstructionJob {
+                    unit_type,
+                    requested: location,
+                    location: placement,
+                    drone: w.tag(),
+                    key,
+                    ordered_at: iteration,
+                });
+                w.build(unit_type, placement, false);
                 self.subtract_resources(unit_type, false);
-                return true;
+                self.state.budget.release(key);
+                return Some(w.tag());
             }
         }
-        false
+        None
+    }
+
+    /// Gas still needed by planned production that hasn't been issued yet
+    fn upcoming_gas_needs(&self) -> u32 {
+        let queued: u32 = self
+            .state
+            .production
+            .items
+            .iter()
+            .filter(|item| item.status != ProductionStatus::Issued)
+            .map(|item| self.production_cost(item.kind).1)
+            .sum();
+        let (_, reserved) = self.state.budget.reserved_excluding(None);
+        queued + reserved
+    }
+
+    /// Takes drones off gas while we're banking it, and puts them back on as soon as the
+    /// production plan needs more than we have, e.g. right before a tech switch
+    pub fn plan_gas_workers(&mut self) {
+        let recheck_interval = 10.0;
+        if self.time < self.state.gas_workers_updated_at + recheck_interval {
+            return;
+        }
+        let gas_slots: usize = self.base_saturations().iter().map(|s| s.gas_slots).sum();
+        let upcoming = self.upcoming_gas_needs();
+        let banked = self.vespene.saturating_sub(upcoming);
+        let pulled = if upcoming > self.vespene {
+            0
+        } else {
+            gas_halves_pulled(self.state.gas_halves_pulled, banked)
+        };
+        let desired = gas_slots * (GAS_BANK_PULL.len() - pulled) / GAS_BANK_PULL.len();
+        if desired != self.state.desired_gas_workers {
+            println!(
+                "{}: Gas workers {} -> {} (banked {}, upcoming {})",
+                self.current_iteration, self.state.desired_gas_workers, desired, self.vespene, upcoming
+            );
+        }
+        self.state.desired_gas_workers = desired;
+        self.state.gas_halves_pulled = pulled;
+        self.state.gas_workers_updated_at = self.time;
     }
 
     fn current_supply_goal(&self) -> usize {
@@ -217,41 +374,70 @@ impl FaxBot {
     }
 
     pub fn perform_training(&mut self, _iteration: usize) -> SC2Result<()> {
-        let has_roachwarren = self.counter().count(UnitTypeId::RoachWarren) > 0;
-        let has_hydraden = self.counter().count(UnitTypeId::HydraliskDen) > 0;
         let is_mineral_starved = self.minerals < 200 && self.vespene > 800;
         let is_gas_starved = self.vespene < 100 && self.minerals > 600;
-        for l in self.units.my.larvas.idle() {
-            let num_workers =
-                self.supply_workers as usize + self.counter().ordered().count(UnitTypeId::Drone);
-            if self.time > 5.0 && self.calculate_pending_supply() < self.current_supply_goal() {
-                if self.can_afford(UnitTypeId::Overlord, false) {
-                    l.train(UnitTypeId::Overlord, false);
-                }
-            } else if (!self.state.is_under_attack)
-                && num_workers < self.state.desired_workers
-                && self.can_afford(UnitTypeId::Drone, true)
-            {
-                l.train(UnitTypeId::Drone, false);
-            } else if has_hydraden
-                && (self.count_unit(UnitTypeId::Roach) > self.count_unit(UnitTypeId::Hydralisk) || is_mineral_starved)
-            {
-                l.train(UnitTypeId::Hydralisk, false);
-            } else if has_roachwarren && self.can_afford(UnitTypeId::Roach, true) {
-                l.train(UnitTypeId::Roach, false);
-            } else if ((self.state.is_under_attack && !has_hydraden && !has_roachwarren) || is_gas_starved)
-                && self.can_afford(UnitTypeId::Zergling, true)
-            {
-                l.train(UnitTypeId::Zergling, false);
-            }
+        let supply_per_overlord = self
+            .game_data
+            .units
+            .get(&UnitTypeId::Overlord)
+            .unwrap()
+            .food_provided
+            .floor() as usize;
+        let mut pending_supply = self.calculate_pending_supply();
+        let mut num_workers =
+            self.supply_workers as usize + self.counter().ordered().count(UnitTypeId::Drone);
+        let mut planned = HashMap::new();
+        let mut runby_larvae = self.runby_larvae_wanted();
+        for _ in 0..self.units.my.larvas.idle().len() {
+            let (unit_type, priority) =
+                if self.time > 5.0 && pending_supply < self.current_supply_goal() {
+                    pending_supply += supply_per_overlord;
+                    (UnitTypeId::Overlord, priority::SUPPLY)
+                } else if (!self.state.is_under_attack) && num_workers < self.state.desired_workers {
+                    num_workers += 1;
+                    (UnitTypeId::Drone, priority::DRONE)
+                } else if runby_larvae > 0 {
+                    runby_larvae -= 1;
+                    *planned.entry(UnitTypeId::Zergling).or_insert(0) += 1;
+                    (UnitTypeId::Zergling, priority::ARMY)
+                } else if let Some(unit_type) =
+                    self.choose_army_unit(&planned, is_mineral_starved, is_gas_starved)
+                {
+                    *planned.entry(unit_type).or_insert(0) += 1;
+                    (unit_type, priority::ARMY)
+                } else {
+                    break;
+                };
+            self.state.production.push(ProductionKind::Larva(unit_type), priority);
         }
+        self.plan_army_morphs();
+        self.plan_overseers();
+        // One queen per hatchery for injects, plus one to spread creep once we have a natural
+        let creep_queens = (self.units.my.townhalls.len() >= 2) as usize;
         if self.count_unit(UnitTypeId::SpawningPool) > 0
-            && self.count_unit(UnitTypeId::Queen) < self.units.my.townhalls.len()
+            && self.count_unit(UnitTypeId::Queen) < self.units.my.townhalls.len() + creep_queens
         {
-            if let Some(hatch) = self.least_busy_hatch() {
-                hatch.train(UnitTypeId::Queen, true);
-            }
+            self.state.production.push(ProductionKind::Townhall(UnitTypeId::Queen), priority::QUEEN);
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::m_macro::gas_halves_pulled;
+
+    #[test]
+    fn gas_drones_come_off_and_back_at_different_banks() {
+        assert_eq!(gas_halves_pulled(0, 400), 0);
+        assert_eq!(gas_halves_pulled(0, 600), 1);
+        assert_eq!(gas_halves_pulled(0, 1200), 2);
+        // Hovering around the threshold they came off at doesn't put them back
+        assert_eq!(gas_halves_pulled(1, 450), 1);
+        assert_eq!(gas_halves_pulled(2, 950), 2);
+        // Only a clearly lower bank does
+        assert_eq!(gas_halves_pulled(2, 650), 1);
+        assert_eq!(gas_halves_pulled(2, 200), 0);
+        assert_eq!(gas_halves_pulled(1, 250), 0);
+    }
+}
@@ -1,12 +1,29 @@
 #[macro_use]
 extern crate clap;
 
+mod army;
+mod attack;
 mod bot;
+mod budget;
+mod composition;
+mod construction;
+mod defense;
 mod m_macro;
 mod map;
 mod micro;
+mod mining;
+mod overlords;
+mod overseers;
+mod positioning;
+mod production;
+mod queens;
+mod research;
+mod runby;
 mod state;
 mod strategy;
+mod targeting;
+mod tech;
+mod workers;
 
 use rand::prelude::*;
 use rust_sc2::prelude::*;
@@ -22,6 +39,14 @@ fn main() -> SC2Result<()> {
 
     let mut runtime_options = bot::RuntimeOptions::default();
     runtime_options.use_tryhard_mining = !app.is_present("no_tryhard_mining");
+    runtime_options.debug_production = app.is_present("debug_production");
+    runtime_options.attack_waves = match app.value_of("attack_waves") {
+        Some(waves) => waves
+            .split(',')
+            .map(|w| w.trim().parse::<f32>().expect("Can't parse AttackWaves"))
+            .collect(),
+        None => attack::DEFAULT_ATTACK_WAVES.to_vec(),
+    };
     let runtime_options = runtime_options;
     let mut bot = bot::FaxBot::new(runtime_options);
     bot.set_game_step(game_step);
@@ -79,6 +104,19 @@ fn main() -> SC2Result<()> {
                 save_replay_as: sub.value_of("save_replay"),
             },
         ),
+        ("mining", Some(sub)) => {
+            let pathing = sub.value_of("pathing").unwrap();
+            let layout = mining::simulator::Layout::from_pathing(
+                &std::fs::read_to_string(pathing).expect("Can't read pathing grid"),
+            );
+            let seconds = sub
+                .value_of("seconds")
+                .unwrap()
+                .parse()
+                .expect("Can't parse seconds");
+            mining::simulator::print_report(&layout, game_step as usize, seconds);
+            Ok(())
+        }
         _ => run_ladder_game(
             &mut bot,
             app.value_of("ladder_server").unwrap_or("127.0.0.1"),
@@ -100,6 +138,8 @@ fn parse_args() -> clap::ArgMatches<'static> {
         (version: crate_version!())
         (author: crate_authors!())
         (@arg no_tryhard_mining: --NoTryhardMining)
+        (@arg debug_production: --DebugProduction "Periodically prints the production queue")
+        (@arg attack_waves: --AttackWaves +takes_value "Comma separated game seconds to attack at")
         (@arg ladder_server: --LadderServer +takes_value)
         (@arg opponent_id: --OpponentId +takes_value)
         (@arg host_port: --GamePort +takes_value)
@@ -158,6 +198,19 @@ fn parse_args() -> clap::ArgMatches<'static> {
                 "Sets path to save replay"
             )
         )
+        (@subcommand mining =>
+            (about: "Simulates mining a base with and without tryhard mining")
+            (@arg pathing: -p --pathing
+                +takes_value
+                default_value("tests/map1.txt")
+                "Sets pathing grid to take the base from"
+            )
+            (@arg seconds: --seconds
+                +takes_value
+                default_value("300")
+                "Sets game seconds to simulate"
+            )
+        )
     )
         .get_matches()
 }
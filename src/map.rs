@@ -3,6 +3,7 @@ use ndarray::Array2;
 use rust_sc2::geometry::Rect;
 use rust_sc2::pixel_map::{Pixel, PixelMap};
 use rust_sc2::prelude::*;
+use std::collections::VecDeque;
 
 fn trim_array<T: Clone + Default>(data: &Array2<T>, bounds: Rect) -> Array2<T> {
     let width = data.len_of(ndarray::Axis(0));
@@ -50,6 +51,78 @@ fn partition(pathable: &PixelMap) -> Array2<u16> {
     partition
 }
 
+/// How far from a blocked tile, like one under a townhall, we look for a pathable one
+const MAX_UNBLOCK_DISTANCE: isize = 6;
+
+type Tile = (usize, usize);
+
+fn is_pathable(pathable: &PixelMap, tile: Tile) -> bool {
+    pathable[tile] == Pixel::Empty
+}
+
+fn neighbours(pathable: &PixelMap, tile: Tile, distance: isize) -> impl Iterator<Item = Tile> {
+    let (width, height) = (pathable.shape()[0] as isize, pathable.shape()[1] as isize);
+    let (x, y) = (tile.0 as isize, tile.1 as isize);
+    (-distance..=distance)
+        .flat_map(move |dx| (-distance..=distance).map(move |dy| (x + dx, y + dy)))
+        .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width && ny < height)
+        .map(|(nx, ny)| (nx as usize, ny as usize))
+}
+
+/// Closest pathable tile to `tile`, which is blocked itself if there's a building on it
+fn nearest_pathable(pathable: &PixelMap, tile: Tile) -> Option<Tile> {
+    (0..=MAX_UNBLOCK_DISTANCE).find_map(|distance| {
+        neighbours(pathable, tile, distance).find(|&t| is_pathable(pathable, t))
+    })
+}
+
+/// Shortest 8-connected path of pathable tiles, empty if there is none
+fn ground_path(pathable: &PixelMap, from: Tile, to: Tile) -> Vec<Tile> {
+    let (from, to) = match (nearest_pathable(pathable, from), nearest_pathable(pathable, to)) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return vec![],
+    };
+    let mut previous = Array2::<Option<Tile>>::default(pathable.raw_dim());
+    let mut queue = VecDeque::new();
+    previous[from] = Some(from);
+    queue.push_back(from);
+    while let Some(tile) = queue.pop_front() {
+        if tile == to {
+            let mut path = vec![to];
+            while *path.last().unwrap() != from {
+                path.push(previous[*path.last().unwrap()].unwrap());
+            }
+            path.reverse();
+            return path;
+        }
+        for next in neighbours(pathable, tile, 1) {
+            if previous[next].is_none() && is_pathable(pathable, next) {
+                previous[next] = Some(tile);
+                queue.push_back(next);
+            }
+        }
+    }
+    vec![]
+}
+
+/// Pathable tiles within `radius` of `tile`. Chokes have few.
+fn open_tiles(pathable: &PixelMap, tile: Tile, radius: usize) -> usize {
+    neighbours(pathable, tile, radius as isize)
+        .filter(|&(x, y)| {
+            let (dx, dy) = (x as f32 - tile.0 as f32, y as f32 - tile.1 as f32);
+            dx * dx + dy * dy <= (radius * radius) as f32 && is_pathable(pathable, (x, y))
+        })
+        .count()
+}
+
+/// The first of the tiles with the least open ground around it
+fn narrowest_tile(pathable: &PixelMap, tiles: &[Tile], radius: usize) -> Option<Tile> {
+    tiles
+        .iter()
+        .copied()
+        .min_by_key(|&t| open_tiles(pathable, t, radius))
+}
+
 pub struct TrimmedPoint {
     point: Point2,
 }
@@ -92,6 +165,30 @@ impl MapInfo {
         })
     }
 
+    fn tile(&self, point: Point2) -> Tile {
+        let point = self.normalize_point(point).point;
+        (
+            (point.x as usize).min(self.width - 1),
+            (point.y as usize).min(self.height - 1),
+        )
+    }
+    fn tile_center(&self, tile: Tile) -> Point2 {
+        self.extract_point(TrimmedPoint {
+            point: Point2::new(tile.0 as f32 + 0.5, tile.1 as f32 + 0.5),
+        })
+    }
+    /// Ground path from `from` to `to` as tile centers, empty if they aren't connected
+    pub fn ground_path(&self, from: Point2, to: Point2) -> Vec<Point2> {
+        ground_path(&self.pathable_tiles, self.tile(from), self.tile(to))
+            .into_iter()
+            .map(|t| self.tile_center(t))
+            .collect()
+    }
+    /// The point of `points` with the least open ground within `radius`
+    pub fn narrowest_point(&self, points: &[Point2], radius: usize) -> Option<Point2> {
+        let tiles = points.iter().map(|&p| self.tile(p)).collect::<Vec<_>>();
+        narrowest_tile(&self.pathable_tiles, &tiles, radius).map(|t| self.tile_center(t))
+    }
     pub fn get_random_point(&self) -> Point2 {
         self.extract_point(TrimmedPoint {
             point: Point2 {
@@ -104,7 +201,7 @@ impl MapInfo {
 
 #[cfg(test)]
 mod test {
-    use crate::map::partition;
+    use crate::map::{ground_path, narrowest_tile, partition};
     use ndarray::Array2;
     use rust_sc2::pixel_map::{Pixel, PixelMap};
     use std::fs::File;
@@ -148,4 +245,36 @@ mod test {
         println!("{:#?}", partition_map);
         Ok(())
     }
+
+    #[test]
+    fn path_goes_through_choke() {
+        // Two rooms joined by a corridor two tiles wide
+        let map = read_map_from_bytes(
+            b"1111111111111111111\n\
+              1000000111110000001\n\
+              1000000111110000001\n\
+              1000000000000000001\n\
+              1000000000000000001\n\
+              1000000111110000001\n\
+              1000000111110000001\n\
+              1111111111111111111\n",
+        );
+        let path = ground_path(&map, (2, 2), (16, 5));
+        assert_eq!(path.first(), Some(&(2, 2)));
+        assert_eq!(path.last(), Some(&(16, 5)));
+        assert!(path.windows(2).all(|w| {
+            (w[0].0 as isize - w[1].0 as isize).abs() <= 1 && (w[0].1 as isize - w[1].1 as isize).abs() <= 1
+        }));
+        let choke = narrowest_tile(&map, &path, 3).unwrap();
+        assert!((7..=11).contains(&choke.0) && (3..=4).contains(&choke.1));
+    }
+
+    #[test]
+    fn blocked_endpoints_use_nearby_tiles() {
+        let map = read_map_from_bytes(b"11111\n10001\n10101\n10001\n11111\n");
+        let path = ground_path(&map, (0, 0), (4, 4));
+        assert_eq!(path.first(), Some(&(1, 1)));
+        assert_eq!(path.last(), Some(&(3, 3)));
+        assert!(!path.contains(&(2, 2)));
+    }
 }
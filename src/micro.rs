@@ -1,211 +1,258 @@
 use rust_sc2::prelude::*;
 
+use crate::attack::ArmyMode;
 use crate::bot::FaxBot;
+use crate::mining::{MiningOrder, MiningTrip};
+use crate::workers::{
+    mark_close_patches, min_cost_assignment, mineral_shares, mineral_targets, DroneTask, MineralBase, MineralPatch,
+    ResourceType, WorkerManager,
+};
+use float_ord::FloatOrd;
 use std::collections::{HashMap, HashSet};
 use rust_sc2::units::Container;
 
-fn count_distinct<T: Eq + std::hash::Hash, It>(it: It) -> HashMap<T, usize> where It: Iterator<Item=T> {
-    let mut occurences = HashMap::new();
-    for v in it {
-        *occurences.entry(v).or_default() += 1;
-    }
-    occurences
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-pub enum ResourceType {
-    Gas,
-    Mineral,
-}
+/// Roaches below this burrow to regenerate, and come back up above `ROACH_UNBURROW_HEALTH`
+const ROACH_BURROW_HEALTH: f32 = 0.35;
+const ROACH_UNBURROW_HEALTH: f32 = 0.9;
+/// Enemies within this range of a roach mean it's still in a fight
+const ROACH_FIGHT_RADIUS: f32 = 10.0;
 
-#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
-pub enum DroneTask {
-    #[default]
-    Idle,
-    Construct {
-        queued_at: usize,
-    },
-    Gather {
-        resource_type: ResourceType,
-        resource_tag: u64,
-        hatch: u64,
-    },
+/// How many workers a base can use and how many it has
+#[derive(Debug, Clone)]
+pub struct BaseSaturation {
+    pub townhall: u64,
+    pub position: Point2,
+    pub minerals: Vec<u64>,
+    pub gas_slots: usize,
+    pub mineral_workers: usize,
 }
 
 #[derive(Default, Debug)]
 pub struct MicroState {
     pub enemy_base_locations_by_expansion_order: Vec<Point2>,
-    // TODO: Should be a struct
-    pub drones: HashMap<u64, DroneTask>,
-    pub drone_last_seen: HashMap<u64, usize>,
-}
-
-impl MicroState {
-    pub fn possibly_alive_drones(&self, iteration: usize) -> Vec<u64> {
-        let mut possibly_alive = vec![];
-        let gas_drone_timeout_in_ticks = 11 * 3;
-        for (drone, task) in &self.drones {
-            let last_seen = self.drone_last_seen[&drone];
-            if let DroneTask::Gather { resource_type: ResourceType::Gas, .. } = task {
-                if last_seen + gas_drone_timeout_in_ticks > iteration {
-                    possibly_alive.push(*drone);
-                }
-            }
-        }
-        possibly_alive
-    }
+    pub workers: WorkerManager,
+    pub ability_used_at: HashMap<(u64, AbilityId), f32>,
+    pub queens: crate::queens::QueenAssignments,
+    pub overlords: crate::overlords::OverlordPosts,
+    pub runby: crate::runby::Runby,
+    pub defense: crate::positioning::DefensePositions,
+    pub reinforcements: crate::positioning::Reinforcements,
+    pub spent_tumors: HashSet<u64>,
+    /// Damaged units sent home until they've healed
+    pub pulled_back: HashSet<u64>,
 }
 
 impl FaxBot {
     fn idle_drones(&self) -> Units {
-        self.units.my.workers.filter(|u| self.state.micro.drones.get(&u.tag()) == Some(&DroneTask::Idle)).clone()
+        self.units.my.workers.find_tags(&self.state.micro.workers.idle())
+    }
+
+    /// Drones that vanish while mining gas are inside their extractor. Anything else that
+    /// vanishes without a `UnitDestroyed` event has morphed into a building.
+    fn track_vanished_drones(&mut self, iteration: usize) {
+        let visible = self
+            .units
+            .my
+            .workers
+            .ready()
+            .iter()
+            .map(|u| (u.tag(), u.position()))
+            .collect::<Vec<_>>();
+        let extractors = self.units.my.gas_buildings.iter().map(|u| u.tag()).collect::<Vec<_>>();
+        let vanished = self.state.micro.workers.registry.observe(&visible, iteration);
+        self.state.micro.workers.handle_vanished(&vanished, &extractors);
     }
 
     fn micro_drones(&mut self, iteration: usize) -> SC2Result<()> {
         let drones = self.units.my.workers.ready().clone();
-        let mut alive_drones = self.units.my.workers.ready().tags().copied().collect::<HashSet<_>>();
-        // XXX: The API doesn't show us any workers inside gas buildings
-        alive_drones.extend(self.state.micro.possibly_alive_drones(iteration));
-        let alive_drones = alive_drones;
-        self.state.micro.drones.retain(|&k, _| alive_drones.contains(&k));
+        self.track_vanished_drones(iteration);
+        self.state.micro.workers.retain_registered();
         for unit in drones {
-            let tag = unit.tag();
-            if !self.state.micro.drones.contains(&tag) {
+            if self.state.micro.workers.add(unit.tag()) {
                 unit.stop(false);
-                self.state.micro.drones.insert(tag, DroneTask::Idle);
             }
-            self.state.micro.drone_last_seen.insert(tag, iteration);
         }
+        self.defend_with_drones(iteration);
         self.allocate_workers(iteration)
     }
 
-    fn a_move(&self, units: &Units, position: Point2, queue: bool) {
-        for unit in units {
-            unit.attack(Target::Pos(position), queue);
+    /// Enemy workers and proxy structures close to our buildings early in the game
+    fn worker_rush_threats(&self) -> (Units, Units) {
+        if self.time > 60.0 * 6.0 {
+            return (Units::default(), Units::default());
         }
+        let structures = self.units.my.structures.clone();
+        let is_near_us = |u: &Unit| structures.closest_distance(u.position()).is_some_and(|d| d < 15.0);
+        let workers = self.units.enemy.units.filter(|u| u.is_worker() && is_near_us(u));
+        let proxies = self.units.enemy.structures.filter(|u| {
+            matches!(u.type_id(), UnitTypeId::Pylon | UnitTypeId::PhotonCannon | UnitTypeId::Bunker)
+                && is_near_us(u)
+        });
+        (workers, proxies)
     }
 
-    fn determine_most_important_target(&self) -> Point2 {
-        if let Some(unit) = self.units.enemy.all.filter(|u| !u.is_flying()).first() {
-            return unit.position();
+    /// Pulls a few drones per threat against worker and cannon rushes, and sends them back to
+    /// mining once the threat is gone
+    fn defend_with_drones(&mut self, iteration: usize) {
+        let (workers, proxies) = self.worker_rush_threats();
+        // A lone scouting worker isn't worth pulling drones for, unless it's building something
+        let num_workers = if workers.len() >= 3 || !proxies.is_empty() { workers.len() } else { 0 };
+        let threats = workers.iter().chain(proxies.iter()).cloned().collect::<Units>();
+        let desired_defenders = (2 * num_workers + 4 * proxies.len())
+            .min(self.units.my.workers.len().saturating_sub(4));
+        let defenders = self.units.my.workers.filter(|u| {
+            matches!(self.state.micro.workers.task(u.tag()), Some(DroneTask::Defend { .. }))
+        });
+        if desired_defenders == 0 || threats.is_empty() {
+            let pulled_since = defenders
+                .iter()
+                .filter_map(|u| match self.state.micro.workers.task(u.tag()) {
+                    Some(DroneTask::Defend { since }) => Some(since),
+                    _ => None,
+                })
+                .min();
+            if let Some(since) = pulled_since {
+                println!(
+                    "{}: Releasing {} defending drones after {} iterations",
+                    iteration,
+                    defenders.len(),
+                    iteration - since
+                );
+            }
+            for drone in defenders.iter() {
+                self.state.micro.workers.release(drone.tag());
+            }
+            return;
         }
-        for point in self
-            .state
-            .micro
-            .enemy_base_locations_by_expansion_order
-            .iter()
-        {
-            if self.is_hidden(*point) {
-                return *point;
+        let mut num_defenders = defenders.len();
+        for drone in defenders.iter() {
+            // Hurt drones go back to mining, they don't win fights
+            if drone.health().unwrap_or(0) <= 10 && num_defenders > 1 {
+                self.state.micro.workers.release(drone.tag());
+                num_defenders -= 1;
+            }
+        }
+        if num_defenders < desired_defenders {
+            let center = threats.center().unwrap_or(self.start_location);
+            let mut candidates = self.units.my.workers.filter(|u| {
+                matches!(
+                    self.state.micro.workers.task(u.tag()),
+                    Some(DroneTask::Gather { resource_type: ResourceType::Mineral, .. }) | Some(DroneTask::Idle)
+                ) && u.health().unwrap_or(0) > 10
+            });
+            println!("{}: Pulling {} drones to defend", iteration, desired_defenders - num_defenders);
+            for _ in num_defenders..desired_defenders {
+                if let Some(drone) = candidates.closest(center).cloned() {
+                    candidates.remove(drone.tag());
+                    self.state.micro.workers.assign(drone.tag(), DroneTask::Defend { since: iteration });
+                } else {
+                    break;
+                }
+            }
+        }
+        // Workers first since they fight back, then whatever they're building
+        for drone in self.units.my.workers.iter() {
+            if !matches!(self.state.micro.workers.task(drone.tag()), Some(DroneTask::Defend { .. })) {
+                continue;
+            }
+            let target = workers
+                .closest(drone)
+                .or_else(|| proxies.closest(drone));
+            if let Some(target) = target {
+                if drone.target_tag() != Some(target.tag()) {
+                    drone.attack(Target::Tag(target.tag()), false);
+                }
             }
         }
-        self.state.map_info.get_random_point()
     }
 
-    fn move_overlords(&mut self, _iteration: usize) -> SC2Result<()> {
-        if _iteration < 22 {
-            let midpoint = self.state.map_info.midpoint();
-            let overlords = self
-                .units
-                .my
-                .units
-                .filter(|u| u.type_id() == UnitTypeId::Overlord);
-            for unit in overlords {
-                unit.move_to(Target::Pos(midpoint), false);
-            }
+    pub fn a_move(&self, units: &Units, position: Point2, queue: bool) {
+        for unit in units {
+            unit.attack(Target::Pos(position), queue);
         }
-        Ok(())
     }
 
-    pub fn perform_micro(&mut self, iteration: usize) -> SC2Result<()> {
-        self.move_overlords(iteration)?;
-        let mut army_types = vec![
-            UnitTypeId::Zergling,
-            UnitTypeId::Roach,
-            UnitTypeId::Hydralisk,
-        ];
-        let army_count =
-            self.counter().count(UnitTypeId::Roach) + self.counter().count(UnitTypeId::Hydralisk);
-        if self.state.is_under_attack
-            || army_count > self.state.peak_army
-            || self.supply_used >= 150
-        {
-            if self.state.is_under_attack {
-                army_types.push(UnitTypeId::Zergling);
-            }
-            let active_army_count = self
-                .units
-                .my
-                .units
-                .filter(|u| army_types.contains(&u.type_id()))
-                .len();
-            let army = &mut self
-                .units
-                .my
-                .units
-                .filter(|u| army_types.contains(&u.type_id()))
-                .idle();
-            let target = self.determine_most_important_target();
-            if !army.is_empty() {
-                println!(
-                    "A-moving {}/{} units to {:?}",
-                    army.len(),
-                    active_army_count,
-                    target
-                );
-                self.a_move(army, target, false);
-            }
-            self.state.peak_army = army_count;
+    pub fn can_burrow_roaches(&self) -> bool {
+        self.has_upgrade(UpgradeId::Burrow) && self.has_upgrade(UpgradeId::TunnelingClaws)
+    }
+
+    /// Roaches low on health burrow to regenerate, and tunnel away from enemies on top of them.
+    /// They come back up once healed or once the fight is over. Returns the roaches it ordered.
+    fn burrow_roaches(&mut self) -> Vec<u64> {
+        let mut ordered = vec![];
+        if !self.can_burrow_roaches() {
+            return ordered;
         }
-        {
-            self.position_queens();
-            let idle_hatcheries = self
-                .units
-                .my
-                .townhalls
-                .filter(|hatch| !hatch.buffs().contains(&BuffId::QueenSpawnLarvaTimer));
-            let ready_queens = self.units.my.units.idle().filter(|q| {
-                q.type_id() == UnitTypeId::Queen
-                    && q.energy().unwrap() as usize
-                    >= self.energy_cost(AbilityId::EffectInjectLarva).unwrap()
-            });
-            for hatch in idle_hatcheries.iter() {
-                if let Some(queen) = ready_queens.filter(|q| q.distance(hatch) <= 8.0).first() {
-                    queen.command(AbilityId::EffectInjectLarva, Target::Tag(hatch.tag()), true);
+        let enemies = self.units.enemy.all.filter(|e| e.can_attack_ground() && !e.is_structure());
+        let roaches = self
+            .units
+            .my
+            .units
+            .filter(|u| matches!(u.type_id(), UnitTypeId::Roach | UnitTypeId::RoachBurrowed));
+        for roach in roaches.iter() {
+            let health = roach.health_percentage().unwrap_or(1.0);
+            let in_fight = !enemies.closer(ROACH_FIGHT_RADIUS, roach).is_empty();
+            if roach.type_id() == UnitTypeId::Roach {
+                if health < ROACH_BURROW_HEALTH && in_fight {
+                    roach.use_ability(AbilityId::BurrowDownRoach, false);
+                    ordered.push(roach.tag());
                 }
+            } else if health >= ROACH_UNBURROW_HEALTH || !in_fight {
+                roach.use_ability(AbilityId::BurrowUpRoach, false);
+                ordered.push(roach.tag());
+            } else if let Some(enemy) = enemies.closer(3.0, roach).closest(roach) {
+                let away = (roach.position() - enemy.position()).normalize();
+                roach.move_to(Target::Pos(roach.position() + away * 3.0), false);
+                ordered.push(roach.tag());
             }
         }
+        ordered
+    }
+
+    pub fn perform_micro(&mut self, iteration: usize) -> SC2Result<()> {
+        self.micro_overlords();
+        let mut busy = self.burrow_roaches();
+        if self.state.attack.mode == ArmyMode::Retreating {
+            busy.extend(self.attack_army().tags());
+        }
+        self.micro_army(&busy);
+        self.micro_runby();
+        self.dispatch_reinforcements();
+        self.command_army();
+        self.micro_queens();
+        self.micro_overseers();
         self.micro_drones(iteration)?;
         Ok(())
     }
 
-    fn position_queens(&mut self) {
-        let mut unqueened_hatches = vec![];
-        let mut queens = self
+    /// Keeps each queen in front of the hatchery it's assigned to, where it's out of the way of
+    /// the drones. Unassigned queens and the ones in `busy`, which got orders this step, are left
+    /// alone.
+    pub fn position_queens(&mut self, busy: &[u64]) {
+        let queens = self
             .units
             .my
             .units
             .filter(|u| u.type_id() == UnitTypeId::Queen)
-            .idle();
-        for hatch in self.units.my.townhalls.iter() {
-            if let Some(nearest_queen) = queens
-                .filter(|u| u.distance(hatch) < 8.0)
-                .closest(hatch)
+            .idle()
+            .filter(|q| !busy.contains(&q.tag()));
+        for queen in queens.iter() {
+            let hatch = match self
+                .state
+                .micro
+                .queens
+                .assigned_hatch(queen.tag())
+                .and_then(|h| self.units.my.townhalls.get(h))
             {
-                queens.remove(nearest_queen.tag());
-                let hatch_pos = hatch.position();
-                let queen_pos = nearest_queen.position();
-                let vec_away = self.vec_away_from_resources(hatch_pos);
-                if (queen_pos - hatch_pos).dot(vec_away) < 0.0 {
-                    nearest_queen.attack(Target::Pos(hatch_pos + vec_away * hatch.radius()), false);
-                }
-            } else {
-                unqueened_hatches.push(hatch);
-            }
-        }
-        for hatch in unqueened_hatches.iter() {
-            if let Some(queen) = queens.pop() {
-                queen.attack(Target::Pos(hatch.position()), false);
+                Some(hatch) => hatch,
+                None => continue,
+            };
+            let hatch_pos = hatch.position();
+            let queen_pos = queen.position();
+            let vec_away = self.vec_away_from_resources(hatch_pos);
+            // Stand on the side of the hatchery facing away from the mineral line
+            if queen.distance(hatch) >= 8.0 || (queen_pos - hatch_pos).dot(vec_away) < 0.0 {
+                queen.attack(Target::Pos(hatch_pos + vec_away * hatch.radius()), false);
             }
         }
     }
@@ -224,13 +271,9 @@ impl FaxBot {
         }).collect()
     }
 
-    fn place_drones_on_resource(&mut self, hatch: Unit, resource: Unit, resource_type: ResourceType, currently_assigned: usize, max_assignable: usize) -> SC2Result<()> {
+    fn place_drones_on_resource(&mut self, hatch: Unit, resource: Unit, resource_type: ResourceType, currently_assigned: usize, max_assignable: usize, desired_for_resource: usize) -> SC2Result<()> {
         let mut surplus_workers = self.idle_drones();
         let mut to_assign = vec![];
-        let desired_for_resource = match resource_type {
-            ResourceType::Mineral => 2usize,
-            ResourceType::Gas => 3usize,
-        };
         if currently_assigned < desired_for_resource {
             let to_add = desired_for_resource.saturating_sub(currently_assigned)
                 .min(surplus_workers.len())
@@ -243,77 +286,124 @@ impl FaxBot {
             }
         } else {
             let to_remove = currently_assigned - desired_for_resource;
-            let workers_on_resource = self.state.micro.drones.iter().filter_map(|(&u, &task)| {
-                match task {
-                    DroneTask::Gather { resource_type: r, resource_tag, .. } if r == resource_type && resource_tag == resource.tag() => Some(u),
-                    _ => None
-                }
-            });
-            for worker in workers_on_resource.take(to_remove) {
+            for worker in self.state.micro.workers.on_resource(resource.tag()).into_iter().take(to_remove) {
                 to_assign.push((worker, DroneTask::Idle));
             }
         }
         for (worker, task) in to_assign {
-            self.state.micro.drones.insert(worker, task);
+            self.state.micro.workers.assign(worker, task);
         }
         Ok(())
     }
 
+    /// Splits the mineral drones between bases by where they are, then fills each base's patches
+    /// with the least total travel for the idle drones. Close patches take two drones before far
+    /// ones do, and a third only when there are drones to spare.
+    fn place_drones_on_minerals(&mut self, minerals: &[(Unit, Unit)], mineral_workers: usize) {
+        let mut patches = minerals
+            .iter()
+            .map(|(hatch, patch)| MineralPatch {
+                tag: patch.tag(),
+                hatch: hatch.tag(),
+                position: patch.position(),
+                distance: hatch.distance(patch),
+                close: false,
+            })
+            .collect::<Vec<_>>();
+        mark_close_patches(&mut patches);
+        let drones = self.idle_drones();
+        let saturations = self.base_saturations();
+        let closest_base = |position: Point2| {
+            saturations
+                .iter()
+                .min_by_key(|s| FloatOrd(s.position.distance(position)))
+                .map(|s| s.townhall)
+        };
+        let bases = saturations
+            .iter()
+            .map(|s| MineralBase {
+                hatch: s.townhall,
+                position: s.position,
+                patches: s.minerals.len(),
+                close_patches: patches.iter().filter(|p| p.hatch == s.townhall && p.close).count(),
+                workers: s.mineral_workers
+                    + drones
+                        .iter()
+                        .filter(|d| closest_base(d.position()) == Some(s.townhall))
+                        .count(),
+            })
+            .collect::<Vec<_>>();
+        let shares = mineral_shares(&bases, mineral_workers);
+        let mut targets = HashMap::new();
+        for base in bases.iter() {
+            let base_patches = patches.iter().filter(|p| p.hatch == base.hatch).cloned().collect::<Vec<_>>();
+            targets.extend(mineral_targets(&base_patches, shares[&base.hatch]));
+        }
+        let mut slots = vec![];
+        for patch in patches.iter() {
+            let assigned = self.state.micro.workers.on_resource(patch.tag);
+            let target = targets.get(&patch.tag).copied().unwrap_or(0);
+            for &drone in assigned.iter().skip(target) {
+                self.state.micro.workers.release(drone);
+            }
+            for _ in assigned.len()..target {
+                slots.push(patch);
+            }
+        }
+        let costs = drones
+            .iter()
+            .map(|d| slots.iter().map(|p| d.distance(p.position)).collect())
+            .collect::<Vec<Vec<f32>>>();
+        let mut transfers: HashMap<u64, usize> = HashMap::new();
+        for (drone, slot) in drones.iter().zip(min_cost_assignment(&costs)) {
+            if let Some(slot) = slot {
+                let patch = slots[slot];
+                if closest_base(drone.position()) != Some(patch.hatch) {
+                    *transfers.entry(patch.hatch).or_insert(0) += 1;
+                }
+                self.state
+                    .micro
+                    .workers
+                    .assign_gather(drone.tag(), patch.hatch, ResourceType::Mineral, patch.tag);
+            }
+        }
+        for (hatch, count) in transfers {
+            println!(
+                "{}: Transferring {} drones to base {} ({} mineral workers wanted)",
+                self.current_iteration, count, hatch, shares[&hatch]
+            );
+        }
+    }
+
     fn place_drones_on_resources(&mut self) -> SC2Result<()> {
         if self.units.my.townhalls.len() == 0 {
             return Ok(());
         }
-        let currently_assigned = count_distinct(self
-            .state
-            .micro
-            .drones
-            .values()
-            .filter_map(|e| if let DroneTask::Gather {
-                resource_tag, ..
-            } = e {
-                Some(*resource_tag)
-            } else {
-                None
-            }));
+        let workers_by_extractor = self.state.micro.workers.gas_workers_by_extractor();
         let available_minerals = self.get_relevant_resources(self.units.mineral_fields.clone());
         let available_gasses = self.get_relevant_resources(self.units.my.gas_buildings.filter(|u| u.vespene_contents().unwrap() > 0).clone());
         let desired_mineral_workers = self.state.desired_workers - 3 * self.state.desired_gasses;
-        let desired_gas_workers = self.units.my.workers.len().saturating_sub(desired_mineral_workers);
-        let resource_something = [
-            (available_minerals, desired_mineral_workers, ResourceType::Mineral, 2usize),
-            (available_gasses, desired_gas_workers, ResourceType::Gas, 3usize),
-        ];
-        let mut desired_workers = 0usize;
-        for (available_resource_patches, desired_workers_for_type, resource_type, desired_for_patch) in resource_something {
-            desired_workers += desired_workers_for_type;
-            for (hatch, resource) in available_resource_patches {
-                if desired_workers == 0 {
-                    break;
-                }
-                let currently_assigned = *currently_assigned.get(&resource.tag()).unwrap_or(&0);
-                let max_assignable = desired_workers.saturating_sub(currently_assigned).min(desired_for_patch);
-                self.place_drones_on_resource(hatch, resource, resource_type, currently_assigned, max_assignable)?;
-                desired_workers = desired_workers.saturating_sub(desired_for_patch);
-            }
+        let mineral_slots = 2 * available_minerals.len();
+        // Mineral slots we couldn't fill spill over to gas, but never more than the production
+        // plan wants. Unlike minerals, gas workers get pulled off again when we have too many.
+        let mut desired_gas_workers = (desired_mineral_workers.saturating_sub(mineral_slots)
+            + self.units.my.workers.len().saturating_sub(desired_mineral_workers))
+            .min(self.state.desired_gas_workers);
+        // Keep enough idle drones around to fill the gas slots that are still open
+        let workers = &self.state.micro.workers;
+        let gas_workers = workers.count_gathering(ResourceType::Gas);
+        let mineral_workers = (workers.count_gathering(ResourceType::Mineral) + workers.idle().len())
+            .saturating_sub(desired_gas_workers.saturating_sub(gas_workers));
+        self.place_drones_on_minerals(&available_minerals, mineral_workers);
+        for (hatch, resource) in available_gasses {
+            let desired_for_resource = desired_gas_workers.min(3);
+            desired_gas_workers -= desired_for_resource;
+            let currently_assigned = *workers_by_extractor.get(&resource.tag()).unwrap_or(&0);
+            self.place_drones_on_resource(hatch, resource, ResourceType::Gas, currently_assigned, desired_for_resource, desired_for_resource)?;
         }
         Ok(())
     }
 
-    fn calc_line(unit1: &Unit, unit2: &Unit) -> (Point2, Point2) {
-        let point1 = unit1.position();
-        let point2 = unit2.position();
-        let rad1 = unit1.radius();
-        let rad2 = unit2.radius();
-        if point1.distance(point2) < rad1 + rad2 {
-            let mid = Point2::new(point1.x + point2.x, point1.y + point2.y) / 2.0;
-            (mid, mid)
-        } else {
-            let delta = point2 - point1;
-            let dir = delta.normalize();
-            (point1 + dir * rad1, point2 - dir * rad2)
-        }
-    }
-
     fn move_drones(&mut self, iteration: usize) -> SC2Result<()> {
         let should_tryhard_mine = self.runtime_options.use_tryhard_mining;
         let workers = self.units.my.workers.clone();
@@ -321,7 +411,10 @@ impl FaxBot {
         let mut num_idle = 0usize;
         let mut num_construct = 0usize;
         for drone in workers {
-            let task = *self.state.micro.drones.get(&drone.tag()).unwrap();
+            let task = match self.state.micro.workers.task(drone.tag()) {
+                Some(task) => task,
+                None => continue,
+            };
             let new_task = match task {
                 DroneTask::Gather { hatch, resource_tag, resource_type } => {
                     let hatch = self.units.all.get(hatch);
@@ -338,11 +431,12 @@ impl FaxBot {
                         } else {
                             let resource = self.units.all.get(resource_tag).unwrap();
                             let hatch = hatch.unwrap();
-                            let hatch_radius = hatch.radius();
-                            let res_radius = resource.radius();
-                            let dist_between_centers = resource.position().distance(hatch.position());
-                            let path_dist = dist_between_centers - hatch_radius - res_radius;
-                            let (hatch_near_pos, resource_near_pos) = Self::calc_line(hatch, resource);
+                            let trip = MiningTrip {
+                                townhall: hatch.position(),
+                                townhall_radius: hatch.radius(),
+                                resource: resource.position(),
+                                resource_radius: resource.radius(),
+                            };
                             let first_order = drone.order();
                             let check_current_order = |ability: AbilityId, target: Target| {
                                 if let Some((a, t, _)) = first_order {
@@ -356,35 +450,23 @@ impl FaxBot {
                                     false
                                 }
                             };
-                            if drone.is_carrying_resource() {
-                                let is_returning = check_current_order(AbilityId::HarvestReturnDrone, Target::None)
-                                    || check_current_order(AbilityId::HarvestReturnDrone, Target::Tag(hatch.tag()));
-                                let distance = drone.position().distance(hatch.position()) - hatch_radius;
-                                if distance >= path_dist * 0.5 {
+                            match trip.tryhard_order(drone.position(), drone.is_carrying_resource()) {
+                                MiningOrder::Return => {
+                                    let is_returning = check_current_order(AbilityId::HarvestReturnDrone, Target::None)
+                                        || check_current_order(AbilityId::HarvestReturnDrone, Target::Tag(hatch.tag()));
                                     if !is_returning {
                                         drone.return_resource(false);
                                     }
-                                } else if distance > 0.5 {
-                                    if !check_current_order(AbilityId::MoveMove, Target::Pos(hatch_near_pos)) {
-                                        drone.move_to(Target::Pos(hatch_near_pos), false);
-                                    }
-                                } else if !is_returning {
-                                    drone.return_resource(false);
                                 }
-                            } else {
-                                let is_gathering = check_current_order(AbilityId::HarvestGatherDrone, Target::Tag(resource_tag));
-                                let distance = drone.position().distance(resource.position()) - res_radius;
-                                let move_target_distance = drone.position().distance(resource_near_pos);
-                                if distance >= path_dist * 0.5 {
-                                    if !is_gathering {
+                                MiningOrder::Gather => {
+                                    if !check_current_order(AbilityId::HarvestGatherDrone, Target::Tag(resource_tag)) {
                                         drone.gather(resource_tag, false);
                                     }
-                                } else if move_target_distance > 0.5 {
-                                    if !check_current_order(AbilityId::MoveMove, Target::Pos(resource_near_pos)) {
-                                        drone.move_to(Target::Pos(resource_near_pos), false);
+                                }
+                                MiningOrder::Move(position) => {
+                                    if !check_current_order(AbilityId::MoveMove, Target::Pos(position)) {
+                                        drone.move_to(Target::Pos(position), false);
                                     }
-                                } else if !is_gathering {
-                                    drone.gather(resource_tag, false);
                                 }
                             }
                             task
@@ -436,12 +518,43 @@ impl FaxBot {
                 _ => task,
             };
             if new_task != task {
-                self.state.micro.drones.insert(drone.tag(), new_task);
+                self.state.micro.workers.assign(drone.tag(), new_task);
             }
         }
         Ok(())
     }
 
+    pub fn base_saturations(&self) -> Vec<BaseSaturation> {
+        if self.units.my.townhalls.ready().is_empty() {
+            return vec![];
+        }
+        let minerals = self.get_relevant_resources(self.units.mineral_fields.clone());
+        let gasses = self.get_relevant_resources(
+            self.units.my.gas_buildings.ready().filter(|u| u.vespene_contents().unwrap() > 0),
+        );
+        self.units
+            .my
+            .townhalls
+            .ready()
+            .iter()
+            .map(|th| {
+                let base_minerals = minerals
+                    .iter()
+                    .filter(|(h, _)| h.tag() == th.tag())
+                    .map(|(_, m)| m.tag())
+                    .collect::<Vec<_>>();
+                let num_gasses = gasses.iter().filter(|(h, _)| h.tag() == th.tag()).count();
+                BaseSaturation {
+                    townhall: th.tag(),
+                    position: th.position(),
+                    minerals: base_minerals,
+                    gas_slots: 3 * num_gasses,
+                    mineral_workers: self.state.micro.workers.count_at_base(th.tag(), ResourceType::Mineral),
+                }
+            })
+            .collect()
+    }
+
     fn allocate_workers(&mut self, iteration: usize) -> SC2Result<()> {
         self.place_drones_on_resources()?;
         self.move_drones(iteration)?;
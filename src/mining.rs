@@ -0,0 +1,66 @@
+use rust_sc2::prelude::*;
+
+pub mod simulator;
+
+/// Fraction of the townhall to patch gap over which drones keep their plain harvest order.
+/// Closer than that they get a move order instead, so they don't slow down on arrival.
+pub const TRYHARD_SWITCH_FRACTION: f32 = 0.5;
+/// How close to the end of the move a drone gets before it's given back its harvest order
+pub const TRYHARD_MOVE_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MiningOrder {
+    Gather,
+    Return,
+    Move(Point2),
+}
+
+/// A townhall and the resource a drone is mining from it
+#[derive(Debug, Copy, Clone)]
+pub struct MiningTrip {
+    pub townhall: Point2,
+    pub townhall_radius: f32,
+    pub resource: Point2,
+    pub resource_radius: f32,
+}
+
+impl MiningTrip {
+    /// Distance between the edges of the townhall and the resource
+    pub fn path_distance(&self) -> f32 {
+        self.townhall.distance(self.resource) - self.townhall_radius - self.resource_radius
+    }
+
+    /// Points on the edges of the townhall and the resource that face each other
+    pub fn edge_points(&self) -> (Point2, Point2) {
+        if self.townhall.distance(self.resource) < self.townhall_radius + self.resource_radius {
+            let mid = (self.townhall + self.resource) / 2.0;
+            (mid, mid)
+        } else {
+            let dir = (self.resource - self.townhall).normalize();
+            (self.townhall + dir * self.townhall_radius, self.resource - dir * self.resource_radius)
+        }
+    }
+
+    /// The order a tryhard mining drone at `drone` should have. Far from the destination it uses
+    /// the normal harvest order, close to it a move order so it arrives at full speed, and right
+    /// at the destination the harvest order again so it starts mining or drops off immediately.
+    pub fn tryhard_order(&self, drone: Point2, is_carrying: bool) -> MiningOrder {
+        let (townhall_near, resource_near) = self.edge_points();
+        let switch_distance = self.path_distance() * TRYHARD_SWITCH_FRACTION;
+        if is_carrying {
+            let distance = drone.distance(self.townhall) - self.townhall_radius;
+            if distance >= switch_distance || distance <= TRYHARD_MOVE_THRESHOLD {
+                MiningOrder::Return
+            } else {
+                MiningOrder::Move(townhall_near)
+            }
+        } else {
+            let distance = drone.distance(self.resource) - self.resource_radius;
+            if distance >= switch_distance || drone.distance(resource_near) <= TRYHARD_MOVE_THRESHOLD {
+                MiningOrder::Gather
+            } else {
+                MiningOrder::Move(resource_near)
+            }
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Deterministic 2D model of drones mining a single base, for tuning the tryhard mining constants
+//! without watching games. Time is counted in game loops, 22.4 of which make a game second.
+
+use crate::mining::{MiningOrder, MiningTrip};
+use crate::workers::{mark_close_patches, mineral_targets, MineralPatch};
+use rust_sc2::prelude::*;
+
+const LOOPS_PER_SECOND: f32 = 22.4;
+/// Drone movement in distance per game second
+const DRONE_SPEED: f32 = 3.94;
+/// Distance per game second squared. This is the unit data's 2.5 taken as is, scaling it to game
+/// seconds like the speed makes drones mine far faster than they do in game. With 2.5 a lone drone
+/// makes about 55 minerals a minute and 16 drones on 8 patches about 840, like in game.
+const DRONE_ACCELERATION: f32 = 2.5;
+const DRONE_RADIUS: f32 = 0.375;
+/// Game seconds a drone spends at the patch, the unit data's 2.786 is in normal speed seconds
+const MINING_TIME: f32 = 2.786 / 1.4;
+const MINERALS_PER_TRIP: u32 = 5;
+/// How close counts as touching the patch or the townhall
+const CONTACT_EPSILON: f32 = 1e-3;
+const TOWNHALL_RADIUS: f32 = 2.75;
+const MINERAL_RADIUS: f32 = 1.125;
+/// Unpathable tiles this close to the townhall that aren't a geyser are taken for minerals
+const MINERAL_SEARCH_RADIUS: f32 = 9.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Strategy {
+    /// A single gather order, drones return the cargo and go back by themselves
+    Gather,
+    Tryhard,
+}
+
+/// A townhall and the mineral patches around it
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub townhall: Point2,
+    pub townhall_radius: f32,
+    pub minerals: Vec<(Point2, f32)>,
+}
+
+impl Layout {
+    /// The base around the first townhall in a pathing grid of `0` for pathable and `1` for
+    /// unpathable tiles, one row per y. The townhall is the 5x5 block with its corners cut,
+    /// geysers are full 3x3 blocks, and the other unpathable tiles near the townhall are paired
+    /// up into 2x1 mineral fields.
+    pub fn from_pathing(text: &str) -> Layout {
+        let grid = text.lines().map(|l| l.trim().as_bytes()).collect::<Vec<_>>();
+        let is_blocked = |x: isize, y: isize| {
+            x >= 0 && y >= 0 && grid.get(y as usize).and_then(|r| r.get(x as usize)) == Some(&b'1')
+        };
+        let is_townhall = |x: isize, y: isize| {
+            (-1..6).all(|dy| {
+                (-1..6).all(|dx| {
+                    let is_corner = (dx == 0 || dx == 4) && (dy == 0 || dy == 4);
+                    let is_inside = (0..5).contains(&dx) && (0..5).contains(&dy) && !is_corner;
+                    is_blocked(x + dx, y + dy) == is_inside
+                })
+            })
+        };
+        let (x0, y0) = (0..grid.len() as isize)
+            .flat_map(|y| (0..grid[y as usize].len() as isize).map(move |x| (x, y)))
+            .find(|&(x, y)| is_townhall(x, y))
+            .expect("Pathing grid has no townhall");
+        let townhall = Point2::new(x0 as f32 + 2.5, y0 as f32 + 2.5);
+        let is_geyser = |x: isize, y: isize| {
+            (-2..1).any(|ox| (-2..1).any(|oy| (0..3).all(|dx| (0..3).all(|dy| is_blocked(x + ox + dx, y + oy + dy)))))
+        };
+        let is_mineral = |x: isize, y: isize| {
+            let center = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+            is_blocked(x, y)
+                && center.distance(townhall) <= MINERAL_SEARCH_RADIUS
+                && !((x0..x0 + 5).contains(&x) && (y0..y0 + 5).contains(&y))
+                && !is_geyser(x, y)
+        };
+        let reach = MINERAL_SEARCH_RADIUS as isize + 1;
+        let mut minerals = vec![];
+        for y in y0 + 2 - reach..=y0 + 2 + reach {
+            let mut x = x0 + 2 - reach;
+            while x <= x0 + 2 + reach {
+                if is_mineral(x, y) && is_mineral(x + 1, y) {
+                    minerals.push((Point2::new(x as f32 + 1.0, y as f32 + 0.5), MINERAL_RADIUS));
+                    x += 2;
+                } else {
+                    x += 1;
+                }
+            }
+        }
+        Layout {
+            townhall,
+            townhall_radius: TOWNHALL_RADIUS,
+            minerals,
+        }
+    }
+
+    fn trip(&self, patch: usize) -> MiningTrip {
+        let (resource, resource_radius) = self.minerals[patch];
+        MiningTrip {
+            townhall: self.townhall,
+            townhall_radius: self.townhall_radius,
+            resource,
+            resource_radius,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Drone {
+    patch: usize,
+    position: Point2,
+    heading: Option<Point2>,
+    speed: f32,
+    is_carrying: bool,
+    mining_left: f32,
+    order: MiningOrder,
+}
+
+struct Simulation<'a> {
+    layout: &'a Layout,
+    strategy: Strategy,
+    drones: Vec<Drone>,
+    /// Which drone is mining each patch, only one can at a time
+    miner: Vec<Option<usize>>,
+    minerals: u32,
+}
+
+impl<'a> Simulation<'a> {
+    /// Drones start next to the townhall and are spread over the patches like the bot does it
+    fn new(layout: &'a Layout, workers: usize, strategy: Strategy) -> Simulation<'a> {
+        let mut patches = layout
+            .minerals
+            .iter()
+            .enumerate()
+            .map(|(i, &(position, _))| MineralPatch {
+                tag: i as u64,
+                hatch: 0,
+                position,
+                distance: layout.townhall.distance(position),
+                close: false,
+            })
+            .collect::<Vec<_>>();
+        mark_close_patches(&mut patches);
+        let targets = mineral_targets(&patches, workers);
+        let mut drones = vec![];
+        for patch in 0..layout.minerals.len() {
+            for _ in 0..targets[&(patch as u64)] {
+                let dir = (layout.minerals[patch].0 - layout.townhall).normalize();
+                drones.push(Drone {
+                    patch,
+                    position: layout.townhall + dir * (layout.townhall_radius + DRONE_RADIUS),
+                    heading: None,
+                    speed: 0.0,
+                    is_carrying: false,
+                    mining_left: 0.0,
+                    order: MiningOrder::Gather,
+                });
+            }
+        }
+        Simulation {
+            layout,
+            strategy,
+            miner: vec![None; layout.minerals.len()],
+            drones,
+            minerals: 0,
+        }
+    }
+
+    /// What the bot does on its step. Plain gathering needs nothing after the first order.
+    fn update_orders(&mut self) {
+        if self.strategy == Strategy::Gather {
+            return;
+        }
+        for drone in self.drones.iter_mut() {
+            drone.order = self.layout.trip(drone.patch).tryhard_order(drone.position, drone.is_carrying);
+        }
+    }
+
+    fn step(&mut self) {
+        let speed = DRONE_SPEED / LOOPS_PER_SECOND;
+        let acceleration = DRONE_ACCELERATION / (LOOPS_PER_SECOND * LOOPS_PER_SECOND);
+        for i in 0..self.drones.len() {
+            let drone = &mut self.drones[i];
+            if drone.mining_left > 0.0 {
+                drone.mining_left -= 1.0;
+                if drone.mining_left <= 0.0 {
+                    drone.is_carrying = true;
+                    self.miner[drone.patch] = None;
+                    if drone.order == MiningOrder::Gather {
+                        drone.order = MiningOrder::Return;
+                    }
+                }
+                continue;
+            }
+            let (resource, resource_radius) = self.layout.minerals[drone.patch];
+            let (townhall, townhall_radius) = (self.layout.townhall, self.layout.townhall_radius);
+            let is_near_resource =
+                |p: Point2| p.distance(resource) - resource_radius <= p.distance(townhall) - townhall_radius;
+            // Everything stops on touching the patch or the townhall, but only harvest orders
+            // slow down to stop there
+            let (target, brakes) = match drone.order {
+                MiningOrder::Gather => (resource, true),
+                MiningOrder::Return => (townhall, true),
+                MiningOrder::Move(p) => (p, false),
+            };
+            let (center, radius) = if is_near_resource(target) {
+                (resource, resource_radius)
+            } else {
+                (townhall, townhall_radius)
+            };
+            let contact = drone.position.distance(center) - radius - DRONE_RADIUS;
+            let remaining = contact.min(drone.position.distance(target));
+            if remaining <= CONTACT_EPSILON {
+                match drone.order {
+                    MiningOrder::Gather if !drone.is_carrying => {
+                        drone.speed = 0.0;
+                        if self.miner[drone.patch].is_none() {
+                            self.miner[drone.patch] = Some(i);
+                            drone.mining_left = MINING_TIME * LOOPS_PER_SECOND;
+                        }
+                    }
+                    MiningOrder::Return if drone.is_carrying => {
+                        drone.is_carrying = false;
+                        drone.order = MiningOrder::Gather;
+                        self.minerals += MINERALS_PER_TRIP;
+                    }
+                    _ => drone.speed = 0.0,
+                }
+                continue;
+            }
+            let dir = (target - drone.position).normalize();
+            // Turning around loses all momentum
+            if drone.heading.is_some_and(|h| h.x * dir.x + h.y * dir.y < 0.0) {
+                drone.speed = 0.0;
+            }
+            drone.heading = Some(dir);
+            let previous = drone.speed;
+            drone.speed = (previous + acceleration).min(speed);
+            if brakes {
+                let stopping_speed = (2.0 * acceleration * remaining).sqrt();
+                drone.speed = drone.speed.min(stopping_speed.max(previous - acceleration));
+            }
+            let travelled = drone.speed.min(remaining);
+            drone.position += dir * travelled;
+            if !brakes && travelled >= remaining {
+                drone.speed = 0.0;
+            }
+        }
+    }
+}
+
+/// Mines for `seconds` with the bot acting every `bot_step` game loops, and returns the income in
+/// minerals per game minute
+pub fn income_per_minute(layout: &Layout, workers: usize, strategy: Strategy, bot_step: usize, seconds: f32) -> f32 {
+    let mut simulation = Simulation::new(layout, workers, strategy);
+    let loops = (seconds * LOOPS_PER_SECOND) as usize;
+    for game_loop in 0..loops {
+        if game_loop % bot_step == 0 {
+            simulation.update_orders();
+        }
+        simulation.step();
+    }
+    simulation.minerals as f32 * 60.0 / seconds
+}
+
+/// Prints the income of both strategies on `layout` at a few saturations
+pub fn print_report(layout: &Layout, bot_step: usize, seconds: f32) {
+    println!(
+        "Townhall at {:?} with {} mineral fields, bot step {}, {} game seconds",
+        layout.townhall,
+        layout.minerals.len(),
+        bot_step,
+        seconds
+    );
+    let patches = layout.minerals.len();
+    for &workers in [patches, patches * 2, patches * 3].iter() {
+        let gather = income_per_minute(layout, workers, Strategy::Gather, bot_step, seconds);
+        let tryhard = income_per_minute(layout, workers, Strategy::Tryhard, bot_step, seconds);
+        println!(
+            "{} workers: gather {:.0}/min, tryhard {:.0}/min ({:+.1}%)",
+            workers,
+            gather,
+            tryhard,
+            (tryhard / gather - 1.0) * 100.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mining::simulator::{income_per_minute, Layout, Strategy};
+    use rust_sc2::prelude::*;
+    use std::fs::read_to_string;
+
+    const BOT_STEP: usize = 2;
+    const SECONDS: f32 = 300.0;
+
+    fn layout() -> Layout {
+        Layout::from_pathing(&read_to_string("tests/map1.txt").unwrap())
+    }
+
+    // Townhall with its top left tile at (1, 3), two mineral fields to its right and a geyser below
+    const BASE: &str = "\
+0000000000000
+0000000000000
+0000000000000
+0011100000000
+0111110011000
+0111110000000
+0111110011000
+0011100000000
+0000000000000
+0001110000000
+0001110000000
+0001110000000
+";
+
+    #[test]
+    fn layout_from_pathing() {
+        let layout = Layout::from_pathing(BASE);
+        assert_eq!(layout.townhall, Point2::new(3.5, 5.5));
+        let minerals = layout.minerals.iter().map(|&(p, _)| p).collect::<Vec<_>>();
+        assert_eq!(minerals, vec![Point2::new(9.0, 4.5), Point2::new(9.0, 6.5)]);
+    }
+
+    #[test]
+    fn map_layout_has_a_mineral_line() {
+        let layout = layout();
+        assert_eq!(layout.minerals.len(), 8);
+        assert!(layout.minerals.iter().all(|(p, _)| p.distance(layout.townhall) < 8.0));
+    }
+
+    #[test]
+    fn both_strategies_mine() {
+        let layout = layout();
+        for &strategy in [Strategy::Gather, Strategy::Tryhard].iter() {
+            assert!(income_per_minute(&layout, 16, strategy, BOT_STEP, SECONDS) > 0.0);
+        }
+    }
+
+    #[test]
+    fn simulation_is_deterministic() {
+        let layout = layout();
+        let first = income_per_minute(&layout, 16, Strategy::Tryhard, BOT_STEP, 60.0);
+        assert_eq!(first, income_per_minute(&layout, 16, Strategy::Tryhard, BOT_STEP, 60.0));
+    }
+
+    #[test]
+    fn income_rises_up_to_saturation() {
+        let layout = layout();
+        let patches = layout.minerals.len();
+        for &strategy in [Strategy::Gather, Strategy::Tryhard].iter() {
+            let income = |workers| income_per_minute(&layout, workers, strategy, BOT_STEP, SECONDS);
+            let (one, two, three) = (income(patches), income(2 * patches), income(3 * patches));
+            assert!(two > one * 1.5, "{:?}: {} -> {}", strategy, one, two);
+            // A third drone on a patch still helps, just not as much
+            assert!(three > two * 1.05, "{:?}: {} -> {}", strategy, two, three);
+        }
+    }
+
+    #[test]
+    fn tryhard_beats_gather() {
+        let layout = layout();
+        for &workers in [8, 16].iter() {
+            let gather = income_per_minute(&layout, workers, Strategy::Gather, BOT_STEP, SECONDS);
+            let tryhard = income_per_minute(&layout, workers, Strategy::Tryhard, BOT_STEP, SECONDS);
+            assert!(tryhard > gather * 1.05, "{} workers: {} vs {}", workers, tryhard, gather);
+        }
+    }
+
+    #[test]
+    fn saturation_has_diminishing_returns() {
+        let layout = layout();
+        let income = |workers| income_per_minute(&layout, workers, Strategy::Gather, BOT_STEP, SECONDS);
+        let (eight, sixteen, twenty_four) = (income(8), income(16), income(24));
+        assert!(sixteen > eight);
+        assert!(twenty_four - sixteen < sixteen - eight);
+    }
+}
@@ -0,0 +1,136 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use std::collections::HashMap;
+
+/// Extra distance on top of an enemy's anti-air range at which overlords start running
+const THREAT_MARGIN: f32 = 3.0;
+/// Overlords this close to a spore are safe enough
+const SPORE_COVER_RADIUS: f32 = 4.0;
+/// How long a watch point we had to flee from stays empty
+const UNSAFE_POST_DURATION: usize = 22 * 90;
+
+/// Where each overlord is posted, and posts we had to run from recently
+#[derive(Debug, Default)]
+pub struct OverlordPosts {
+    post_by_overlord: HashMap<u64, Point2>,
+    unsafe_until: Vec<(Point2, usize)>,
+}
+
+impl OverlordPosts {
+    fn is_taken(&self, post: Point2) -> bool {
+        self.post_by_overlord.values().any(|p| p.distance(post) < 1.0)
+    }
+    fn is_unsafe(&self, post: Point2, iteration: usize) -> bool {
+        self.unsafe_until
+            .iter()
+            .any(|&(p, until)| until > iteration && p.distance(post) < 1.0)
+    }
+    /// Gives up the overlord's post and keeps others away from it for a while
+    fn abandon(&mut self, overlord: u64, iteration: usize) {
+        if let Some(post) = self.post_by_overlord.remove(&overlord) {
+            self.unsafe_until.retain(|&(_, until)| until > iteration);
+            self.unsafe_until.push((post, iteration + UNSAFE_POST_DURATION));
+        }
+    }
+}
+
+impl FaxBot {
+    /// The map center to see attacks coming, then every expansion on our side of the map from
+    /// ours outwards. Expansions closer to the enemy main just get overlords killed.
+    fn overlord_watch_points(&self) -> Vec<Point2> {
+        let distance_to_us = |p: Point2| {
+            self.state
+                .bases
+                .iter()
+                .map(|b| b.distance(p))
+                .fold(p.distance(self.start_location), f32::min)
+        };
+        let mut expansions = self
+            .expansions
+            .iter()
+            .filter(|e| e.alliance != Alliance::Enemy && distance_to_us(e.loc) < e.loc.distance(self.enemy_start))
+            .map(|e| e.loc)
+            .collect::<Vec<_>>();
+        expansions.sort_by_key(|e| float_ord::FloatOrd(e.distance(self.start_location)));
+        let mut points = vec![self.state.map_info.midpoint()];
+        points.extend(expansions);
+        points
+    }
+
+    /// The closest enemy that can shoot the overlord down or is about to be able to
+    pub fn anti_air_threat<'a>(&self, overlord: &Unit, enemies: &'a Units) -> Option<&'a Unit> {
+        enemies
+            .iter()
+            .filter(|e| {
+                e.distance(overlord) <= e.air_range() + e.radius() + overlord.radius() + THREAT_MARGIN
+            })
+            .closest(overlord)
+    }
+
+    /// Closest spore, or the closest townhall if we have no spores
+    pub fn overlord_retreat_position(&self, overlord: &Unit) -> Option<Point2> {
+        let spores = self
+            .units
+            .my
+            .structures
+            .filter(|u| u.type_id() == UnitTypeId::SporeCrawler && u.is_ready());
+        spores
+            .closest(overlord)
+            .or_else(|| self.units.my.townhalls.closest(overlord))
+            .map(|u| u.position())
+    }
+
+    /// Overlords under threat retreat to spores and give up their post. The rest spread over
+    /// watch points, and the ones without a post wait in our mineral lines.
+    pub fn micro_overlords(&mut self) {
+        let iteration = self.current_iteration;
+        let overlords = self.units.my.units.filter(|u| u.type_id() == UnitTypeId::Overlord);
+        self.state
+            .micro
+            .overlords
+            .post_by_overlord
+            .retain(|o, _| overlords.contains_tag(*o));
+        let anti_air = self.units.enemy.all.filter(|u| u.can_attack_air());
+        let mut safe = vec![];
+        for overlord in overlords.iter() {
+            if self.anti_air_threat(overlord, &anti_air).is_none() {
+                safe.push(overlord);
+                continue;
+            }
+            if self.state.micro.overlords.post_by_overlord.contains_key(&overlord.tag()) {
+                println!("{}: Overlord {} is under threat, retreating", iteration, overlord.tag());
+                self.state.micro.overlords.abandon(overlord.tag(), iteration);
+            }
+            if let Some(retreat) = self.overlord_retreat_position(overlord) {
+                if overlord.distance(retreat) > SPORE_COVER_RADIUS {
+                    overlord.move_to(Target::Pos(retreat), false);
+                }
+            }
+        }
+        let mut free_posts = self
+            .overlord_watch_points()
+            .into_iter()
+            .filter(|&p| {
+                !self.state.micro.overlords.is_taken(p) && !self.state.micro.overlords.is_unsafe(p, iteration)
+            })
+            .collect::<Vec<_>>();
+        for overlord in safe {
+            let post = match self.state.micro.overlords.post_by_overlord.get(&overlord.tag()) {
+                Some(&post) => post,
+                None if !free_posts.is_empty() => {
+                    let post = free_posts.remove(0);
+                    self.state.micro.overlords.post_by_overlord.insert(overlord.tag(), post);
+                    post
+                }
+                None => match self.units.my.townhalls.closest(overlord) {
+                    Some(th) => self.mineral_line_defense_position(th.position()),
+                    None => continue,
+                },
+            };
+            if overlord.is_idle() && overlord.distance(post) > 2.0 {
+                overlord.move_to(Target::Pos(post), false);
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::production::{priority, ProductionKind};
+
+/// Army units within this distance of each other move as one squad
+const SQUAD_RADIUS: f32 = 10.0;
+/// Squads smaller than this aren't worth an overseer
+const MIN_SQUAD_SIZE: usize = 4;
+/// How far behind its target an overseer stays, out of the line of fire
+const FOLLOW_DISTANCE: f32 = 3.0;
+
+impl FaxBot {
+    /// Centers of groups of army units, biggest first
+    pub fn army_squads(&self) -> Vec<(Point2, usize)> {
+        let mut army = self
+            .units
+            .my
+            .units
+            .filter(|u| !u.is_worker() && !matches!(u.type_id(), UnitTypeId::Overlord | UnitTypeId::Overseer | UnitTypeId::Queen))
+            .filter(|u| u.can_attack());
+        let mut squads = vec![];
+        while let Some(seed) = army.first().cloned() {
+            let members = army.closer(SQUAD_RADIUS, &seed);
+            for tag in members.tags() {
+                army.remove(*tag);
+            }
+            squads.push((members.center().unwrap_or(seed.position()), members.len()));
+        }
+        squads.sort_by_key(|&(_, size)| std::cmp::Reverse(size));
+        squads
+    }
+
+    /// Where overseers should be: with every real squad, and at the base under threat
+    fn detection_targets(&self) -> Vec<Point2> {
+        let mut targets = self
+            .army_squads()
+            .into_iter()
+            .filter(|&(_, size)| size >= MIN_SQUAD_SIZE)
+            .map(|(center, _)| center)
+            .collect::<Vec<_>>();
+        if let Some(base) = self.threatened_base() {
+            targets.push(base);
+        }
+        targets
+    }
+
+    /// One overseer per detection target once we know the enemy has cloak, otherwise a single
+    /// one to be safe once we have Lair
+    pub fn plan_overseers(&mut self) {
+        if !self.has_tech(UnitTypeId::Lair) {
+            return;
+        }
+        let wanted = if self.state.threats.wants_detection() {
+            self.detection_targets().len().max(1)
+        } else {
+            1
+        };
+        let have = self.counter().all().count(UnitTypeId::Overseer);
+        if have < wanted && self.counter().count(UnitTypeId::Overlord) > 0 {
+            self.state.production.push(
+                ProductionKind::Morph {
+                    from: UnitTypeId::Overlord,
+                    to: UnitTypeId::Overseer,
+                    ability: AbilityId::MorphOverseer,
+                },
+                priority::TECH_MORPH,
+            );
+        }
+    }
+
+    /// Each target gets the closest overseer, spares stay with the biggest squad. Overseers
+    /// alone under anti-air fall back like overlords do.
+    pub fn micro_overseers(&mut self) {
+        let mut overseers = self.units.my.units.filter(|u| u.type_id() == UnitTypeId::Overseer);
+        if overseers.is_empty() {
+            return;
+        }
+        let anti_air = self.units.enemy.all.filter(|u| u.can_attack_air());
+        let army = self.units.my.units.filter(|u| !u.is_worker() && u.can_attack());
+        let targets = self.detection_targets();
+        let mut assignments = vec![];
+        for &target in targets.iter() {
+            if let Some(overseer) = overseers.closest(target).cloned() {
+                overseers.remove(overseer.tag());
+                assignments.push((overseer, target));
+            }
+        }
+        if let Some(&main) = targets.first() {
+            assignments.extend(overseers.iter().map(|o| (o.clone(), main)));
+        }
+        for (overseer, target) in &assignments {
+            let is_covered = !army.closer(6.0, overseer).is_empty();
+            if self.anti_air_threat(overseer, &anti_air).is_some() && !is_covered {
+                if let Some(retreat) = self.overlord_retreat_position(overseer) {
+                    overseer.move_to(Target::Pos(retreat), false);
+                }
+                continue;
+            }
+            let position = target.towards(self.start_location, FOLLOW_DISTANCE);
+            if overseer.distance(position) > 2.0 {
+                overseer.move_to(Target::Pos(position), false);
+            }
+        }
+    }
+}
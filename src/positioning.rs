@@ -0,0 +1,184 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+
+/// Chokes are searched for on the ground path towards the enemy between these distances from
+/// the base, so we don't stand in the mineral line or in the middle of the map
+const MIN_CHOKE_DISTANCE: f32 = 8.0;
+const MAX_CHOKE_DISTANCE: f32 = 25.0;
+/// How much open ground around a point counts towards it being a choke
+const CHOKE_RADIUS: usize = 4;
+/// A base whose path to the enemy goes this close to another of our bases is behind it
+const COVERED_RADIUS: f32 = 12.0;
+/// Army units this close to a defensive position are holding it
+const HOLD_RADIUS: f32 = 8.0;
+/// New army units wait for this many others before walking out together
+const REINFORCEMENT_GROUP_SIZE: usize = 4;
+/// Unless the first of them has been waiting this long
+const REINFORCEMENT_MAX_WAIT: usize = 22 * 20;
+
+/// Where the army waits when it isn't attacking, and the bases they were chosen for along with
+/// each base's ground path to the enemy
+#[derive(Debug, Default)]
+pub struct DefensePositions {
+    bases: Vec<Point2>,
+    paths: Vec<Vec<Point2>>,
+    pub positions: Vec<Point2>,
+    /// Ground paths of other bases we needed an entrance for, since finding them is expensive
+    other_paths: Vec<(Point2, Vec<Point2>)>,
+}
+
+/// New army units that haven't been sent anywhere yet, with the iteration they spawned
+#[derive(Debug, Default)]
+pub struct Reinforcements {
+    waiting: Vec<(u64, usize)>,
+}
+
+impl Reinforcements {
+    pub fn add(&mut self, tag: u64, iteration: usize) {
+        self.waiting.push((tag, iteration));
+    }
+    pub fn remove(&mut self, tag: u64) {
+        self.waiting.retain(|&(t, _)| t != tag);
+    }
+    pub fn contains(&self, tag: u64) -> bool {
+        self.waiting.iter().any(|&(t, _)| t == tag)
+    }
+}
+
+fn same_bases(a: &[Point2], b: &[Point2]) -> bool {
+    a.len() == b.len() && a.iter().all(|p| b.iter().any(|q| p.distance(q) < 1.0))
+}
+
+impl FaxBot {
+    /// The narrowest point on `path` between `min` and `max` from `base`. Without a path we can
+    /// only guess along the straight line to the enemy.
+    fn choke_on_path(&self, base: Point2, path: &[Point2], min: f32, max: f32) -> Point2 {
+        let section = path
+            .iter()
+            .copied()
+            .filter(|p| (min..=max).contains(&p.distance(base)))
+            .collect::<Vec<_>>();
+        self.state
+            .map_info
+            .narrowest_point(&section, CHOKE_RADIUS)
+            .unwrap_or_else(|| base.towards(self.enemy_start, 7.0))
+    }
+
+    /// The narrowest point of the ground path out of `base` towards the enemy, between `min` and
+    /// `max` away from it
+    pub fn base_entrance(&mut self, base: Point2, min: f32, max: f32) -> Point2 {
+        let defense = &self.state.micro.defense;
+        let known = match defense.bases.iter().position(|b| b.distance(base) < 1.0) {
+            Some(i) => Some(defense.paths[i].clone()),
+            None => defense
+                .other_paths
+                .iter()
+                .find(|(b, _)| b.distance(base) < 1.0)
+                .map(|(_, path)| path.clone()),
+        };
+        let path = match known {
+            Some(path) => path,
+            None => {
+                let path = self.state.map_info.ground_path(base, self.enemy_start);
+                self.state.micro.defense.other_paths.push((base, path.clone()));
+                path
+            }
+        };
+        self.choke_on_path(base, &path, min, max)
+    }
+
+    /// The narrowest point on the ground path from each outermost base towards the enemy. Bases
+    /// behind another base are covered by its position.
+    fn choose_defense_positions(&self, bases: &[Point2], paths: &[Vec<Point2>]) -> Vec<Point2> {
+        let mut positions = vec![];
+        for (&base, path) in bases.iter().zip(paths) {
+            let is_covered = bases
+                .iter()
+                .any(|&other| other.distance(base) >= 1.0 && path.iter().any(|p| p.distance(other) < COVERED_RADIUS));
+            if is_covered {
+                continue;
+            }
+            positions.push(self.choke_on_path(base, path, MIN_CHOKE_DISTANCE, MAX_CHOKE_DISTANCE));
+        }
+        if positions.is_empty() {
+            positions.push(self.start_location.towards(self.enemy_start, 7.0));
+        }
+        positions
+    }
+
+    /// Picks new positions when our bases change, and walks the army that was holding the old
+    /// ones forward
+    pub fn update_defense_positions(&mut self) {
+        if same_bases(&self.state.bases, &self.state.micro.defense.bases) {
+            return;
+        }
+        let bases = self.state.bases.clone();
+        let paths = bases
+            .iter()
+            .map(|&b| self.state.map_info.ground_path(b, self.enemy_start))
+            .collect::<Vec<_>>();
+        let positions = self.choose_defense_positions(&bases, &paths);
+        println!(
+            "{}: Defensive positions for {} bases: {:?}",
+            self.current_iteration,
+            bases.len(),
+            positions
+        );
+        let old_positions = std::mem::replace(&mut self.state.micro.defense.positions, positions.clone());
+        self.state.micro.defense.bases = bases;
+        self.state.micro.defense.paths = paths;
+        let holding = self.units.my.units.filter(|u| {
+            !u.is_worker()
+                && u.can_attack()
+                && u.type_id() != UnitTypeId::Queen
+                && u.is_idle()
+                && old_positions.iter().any(|p| u.distance(*p) <= HOLD_RADIUS)
+        });
+        for unit in holding.iter() {
+            if let Some(position) = positions.iter().closest(unit.position()) {
+                unit.attack(Target::Pos(*position), false);
+            }
+        }
+    }
+
+    /// The defensive position closest to the threatened base when under attack, otherwise the
+    /// one with the fewest of our units holding it
+    fn reinforcement_target(&self) -> Point2 {
+        let positions = &self.state.micro.defense.positions;
+        let target = if self.state.is_under_attack {
+            self.threatened_base()
+                .and_then(|base| positions.iter().closest(base).copied())
+        } else {
+            let army = self.units.my.units.filter(|u| !u.is_worker() && u.can_attack());
+            positions
+                .iter()
+                .min_by_key(|p| army.closer(HOLD_RADIUS, **p).len())
+                .copied()
+        };
+        target.unwrap_or(self.start_location)
+    }
+
+    /// New army units wait where they spawned until there are enough of them to walk to a
+    /// defensive position as a group
+    pub fn dispatch_reinforcements(&mut self) {
+        let iteration = self.current_iteration;
+        let reinforcements = &mut self.state.micro.reinforcements;
+        let is_ready = reinforcements.waiting.len() >= REINFORCEMENT_GROUP_SIZE
+            || reinforcements
+                .waiting
+                .first()
+                .is_some_and(|&(_, spawned)| iteration >= spawned + REINFORCEMENT_MAX_WAIT)
+            || (self.state.is_under_attack && !reinforcements.waiting.is_empty());
+        if !is_ready {
+            return;
+        }
+        let tags = reinforcements.waiting.iter().map(|&(t, _)| t).collect::<Vec<_>>();
+        reinforcements.waiting.clear();
+        let target = self.reinforcement_target();
+        println!("{}: Sending {} new units to {:?}", iteration, tags.len(), target);
+        for unit in self.units.my.units.find_tags(&tags).iter() {
+            unit.attack(Target::Pos(target), false);
+        }
+    }
+}
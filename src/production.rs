@@ -0,0 +1,294 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::budget::ReservationKey;
+use float_ord::FloatOrd;
+
+/// Priorities used when feeding the production queue. Higher goes first.
+pub mod priority {
+    pub const SUPPLY: i32 = 100;
+    pub const SPAWNING_POOL: i32 = 90;
+    pub const STATIC_DEFENSE: i32 = 85;
+    pub const EXPANSION: i32 = 80;
+    pub const QUEEN: i32 = 70;
+    pub const GAS: i32 = 65;
+    pub const TECH_STRUCTURE: i32 = 60;
+    pub const TECH_MORPH: i32 = 55;
+    pub const DRONE: i32 = 50;
+    pub const UPGRADE: i32 = 45;
+    pub const ARMY: i32 = 40;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProductionKind {
+    /// A unit morphed from a larva
+    Larva(UnitTypeId),
+    /// A unit trained from a townhall, i.e. queens
+    Townhall(UnitTypeId),
+    Structure {
+        unit_type: UnitTypeId,
+        location: Point2,
+        exact: bool,
+    },
+    Expansion,
+    Gas,
+    Morph {
+        from: UnitTypeId,
+        to: UnitTypeId,
+        ability: AbilityId,
+    },
+    Upgrade {
+        researcher: UnitTypeId,
+        upgrade: UpgradeId,
+        ability: AbilityId,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProductionStatus {
+    Pending,
+    Issued,
+    /// Couldn't be afforded, and reserved its cost from lower priority items
+    Waiting,
+    /// Could be afforded, but no producer or placement was available
+    Blocked,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ProductionItem {
+    pub kind: ProductionKind,
+    pub priority: i32,
+    pub status: ProductionStatus,
+}
+
+#[derive(Debug, Default)]
+pub struct ProductionQueue {
+    pub items: Vec<ProductionItem>,
+}
+
+impl ProductionQueue {
+    pub fn push(&mut self, kind: ProductionKind, priority: i32) {
+        self.items.push(ProductionItem {
+            kind,
+            priority,
+            status: ProductionStatus::Pending,
+        });
+    }
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+    /// Sorts by descending priority, keeping insertion order for equal priorities
+    pub fn sort(&mut self) {
+        self.items.sort_by_key(|i| std::cmp::Reverse(i.priority));
+    }
+}
+
+impl std::fmt::Display for ProductionQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Production queue ({} items):", self.items.len())?;
+        for item in self.items.iter() {
+            writeln!(f, "  [{:>3}] {:?} {:?}", item.priority, item.status, item.kind)?;
+        }
+        Ok(())
+    }
+}
+
+impl FaxBot {
+    pub fn production_cost(&self, kind: ProductionKind) -> (u32, u32) {
+        let unit_cost = |unit_type| {
+            let cost = self.get_unit_cost(unit_type);
+            (cost.minerals, cost.vespene)
+        };
+        match kind {
+            ProductionKind::Larva(unit_type) | ProductionKind::Townhall(unit_type) => {
+                unit_cost(unit_type)
+            }
+            ProductionKind::Structure { unit_type, .. } => unit_cost(unit_type),
+            ProductionKind::Expansion => unit_cost(UnitTypeId::Hatchery),
+            ProductionKind::Gas => unit_cost(UnitTypeId::Extractor),
+            ProductionKind::Morph { to, .. } => unit_cost(to),
+            ProductionKind::Upgrade { upgrade, .. } => {
+                let cost = self.get_upgrade_cost(upgrade);
+                (cost.minerals, cost.vespene)
+            }
+        }
+    }
+
+    /// Returns whether an order was issued
+    fn produce(&mut self, kind: ProductionKind, larvas: &mut Units) -> bool {
+        match kind {
+            ProductionKind::Larva(unit_type) => {
+                if !self.can_afford_budgeted(unit_type, true, None) {
+                    return false;
+                }
+                if let Some(larva) = larvas.pop() {
+                    larva.train(unit_type, false);
+                    self.subtract_resources(unit_type, true);
+                    true
+                } else {
+                    false
+                }
+            }
+            ProductionKind::Townhall(unit_type) => {
+                if !self.can_afford_budgeted(unit_type, true, None) {
+                    return false;
+                }
+                if let Some(hatch) = self.least_busy_hatch() {
+                    hatch.train(unit_type, true);
+                    self.subtract_resources(unit_type, true);
+                    true
+                } else {
+                    false
+                }
+            }
+            ProductionKind::Structure {
+                unit_type,
+                location,
+                exact,
+            } => self.create_building(unit_type, location, exact),
+            ProductionKind::Expansion => {
+                for expansion in self.determine_best_expansion_order() {
+                    if self.take_expansion(expansion) {
+                        return true;
+                    }
+                }
+                false
+            }
+            ProductionKind::Gas => self.ensure_taken_gasses(self.state.desired_gasses),
+            ProductionKind::Morph { from, to, ability } => {
+                let key = ReservationKey::Structure(to);
+                if !self.can_afford_budgeted(to, false, Some(key)) {
+                    return false;
+                }
+                // Army units are rarely idle, so they can be taken off a move or attack. Idle ones
+                // and ones further back from the fight go first.
+                let is_interruptible = |u: &Unit| {
+                    !u.is_structure()
+                        && u.orders().iter().all(|o| {
+                            matches!(o.ability, AbilityId::Attack | AbilityId::AttackAttack | AbilityId::Move | AbilityId::MoveMove)
+                        })
+                };
+                let candidates = self
+                    .units
+                    .my
+                    .all
+                    .filter(|u| u.type_id() == from && u.is_ready() && (u.orders().is_empty() || is_interruptible(u)));
+                let chosen = candidates
+                    .iter()
+                    .min_by_key(|u| (!u.orders().is_empty(), FloatOrd(u.distance(self.start_location))));
+                if let Some(unit) = chosen {
+                    unit.use_ability(ability, false);
+                    self.subtract_resources(to, false);
+                    self.state.budget.release(key);
+                    true
+                } else {
+                    false
+                }
+            }
+            ProductionKind::Upgrade {
+                researcher,
+                upgrade,
+                ability,
+            } => self.research_upgrade(researcher, upgrade, ability),
+        }
+    }
+
+    /// Issues queued production in priority order. Anything that can't be afforded reserves its
+    /// cost, so lower priority items only get to spend what is left over. Money held by the
+    /// budget is never available to anything but the item it was reserved for.
+    pub fn execute_production(&mut self) -> SC2Result<()> {
+        self.state.budget.expire(self.current_iteration);
+        self.state.budget.release_unrequested(self.current_iteration);
+        let mut queue = std::mem::take(&mut self.state.production);
+        queue.sort();
+        let mut larvas = self.units.my.larvas.idle();
+        let mut reserved_minerals = 0u32;
+        let mut reserved_vespene = 0u32;
+        for item in queue.items.iter_mut() {
+            let (minerals, vespene) = self.production_cost(item.kind);
+            let key = ReservationKey::for_production(item.kind);
+            let (budget_minerals, budget_vespene) = self.state.budget.reserved_excluding(key);
+            let available_minerals = self
+                .minerals
+                .saturating_sub(reserved_minerals + budget_minerals);
+            let available_vespene = self
+                .vespene
+                .saturating_sub(reserved_vespene + budget_vespene);
+            item.status = if minerals > available_minerals || vespene > available_vespene {
+                // Budgeted items are already accounted for
+                if !key.is_some_and(|k| self.state.budget.is_reserved(k)) {
+                    reserved_minerals += minerals;
+                    reserved_vespene += vespene;
+                }
+                ProductionStatus::Waiting
+            } else if self.produce(item.kind, &mut larvas) {
+                ProductionStatus::Issued
+            } else {
+                ProductionStatus::Blocked
+            };
+        }
+        if self.runtime_options.debug_production && self.current_iteration % (22 * 5) == 0 {
+            print!("{}: {}", self.current_iteration, queue);
+            println!("  Reserved: {:?}", self.state.budget.reservations);
+        }
+        self.state.production = queue;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::production::{priority, ProductionKind, ProductionQueue, ProductionStatus};
+    use rust_sc2::prelude::UnitTypeId;
+
+    fn kinds(queue: &ProductionQueue) -> Vec<ProductionKind> {
+        queue.items.iter().map(|i| i.kind).collect()
+    }
+
+    #[test]
+    fn sort_by_descending_priority() {
+        let mut queue = ProductionQueue::default();
+        queue.push(ProductionKind::Larva(UnitTypeId::Zergling), priority::ARMY);
+        queue.push(ProductionKind::Larva(UnitTypeId::Overlord), priority::SUPPLY);
+        queue.push(ProductionKind::Larva(UnitTypeId::Drone), priority::DRONE);
+        queue.push(ProductionKind::Expansion, priority::EXPANSION);
+        queue.sort();
+        assert_eq!(
+            kinds(&queue),
+            vec![
+                ProductionKind::Larva(UnitTypeId::Overlord),
+                ProductionKind::Expansion,
+                ProductionKind::Larva(UnitTypeId::Drone),
+                ProductionKind::Larva(UnitTypeId::Zergling),
+            ]
+        );
+        assert!(queue.items.iter().all(|i| i.status == ProductionStatus::Pending));
+    }
+
+    #[test]
+    fn sort_keeps_insertion_order_for_equal_priority() {
+        let mut queue = ProductionQueue::default();
+        queue.push(ProductionKind::Larva(UnitTypeId::Roach), priority::ARMY);
+        queue.push(ProductionKind::Larva(UnitTypeId::Zergling), priority::ARMY);
+        queue.push(ProductionKind::Gas, priority::GAS);
+        queue.push(ProductionKind::Larva(UnitTypeId::Hydralisk), priority::ARMY);
+        queue.sort();
+        assert_eq!(
+            kinds(&queue),
+            vec![
+                ProductionKind::Gas,
+                ProductionKind::Larva(UnitTypeId::Roach),
+                ProductionKind::Larva(UnitTypeId::Zergling),
+                ProductionKind::Larva(UnitTypeId::Hydralisk),
+            ]
+        );
+    }
+
+    #[test]
+    fn clear_empties_the_queue() {
+        let mut queue = ProductionQueue::default();
+        queue.push(ProductionKind::Gas, priority::GAS);
+        queue.clear();
+        assert!(queue.items.is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use std::collections::HashMap;
+
+const TRANSFUSE_RANGE: f32 = 7.0;
+const TRANSFUSE_HEALTH_THRESHOLD: f32 = 0.4;
+const TUMOR_SPREAD_DISTANCE: f32 = 8.0;
+const AIR_HARASS_RADIUS: f32 = 12.0;
+/// How long one inject takes to pop, in game seconds
+const INJECT_DURATION: f32 = 29.0;
+/// How long before the current inject runs out a queen should go for the next one
+const INJECT_LOOKAHEAD: f32 = 2.0;
+
+/// Which queen injects which hatchery. Queens without a hatchery are free for creep and defense.
+#[derive(Debug, Default)]
+pub struct QueenAssignments {
+    hatch_by_queen: HashMap<u64, u64>,
+    inject_ends_at: HashMap<u64, f32>,
+}
+
+impl QueenAssignments {
+    pub fn assign(&mut self, queen: u64, hatch: u64) {
+        self.hatch_by_queen.insert(queen, hatch);
+    }
+    pub fn assigned_hatch(&self, queen: u64) -> Option<u64> {
+        self.hatch_by_queen.get(&queen).copied()
+    }
+    pub fn queen_for(&self, hatch: u64) -> Option<u64> {
+        self.hatch_by_queen
+            .iter()
+            .find(|(_, &h)| h == hatch)
+            .map(|(&q, _)| q)
+    }
+    pub fn assignments(&self) -> Vec<(u64, u64)> {
+        self.hatch_by_queen.iter().map(|(&q, &h)| (q, h)).collect()
+    }
+    pub fn retain<F: Fn(u64, u64) -> bool>(&mut self, f: F) {
+        self.hatch_by_queen.retain(|&q, &mut h| f(q, h));
+        let hatches = self.hatch_by_queen.values().copied().collect::<Vec<_>>();
+        self.inject_ends_at.retain(|h, _| hatches.contains(h));
+    }
+    /// Drops any assignment involving `tag`, so the hatchery can be handed to another queen
+    pub fn forget(&mut self, tag: u64) {
+        if let Some(hatch) = self.hatch_by_queen.remove(&tag) {
+            println!("Queen {} died, hatchery {} needs a new one", tag, hatch);
+        }
+        self.hatch_by_queen.retain(|_, &mut h| h != tag);
+        self.inject_ends_at.remove(&tag);
+    }
+    pub fn inject_ends_at(&self, hatch: u64) -> f32 {
+        self.inject_ends_at.get(&hatch).copied().unwrap_or(0.0)
+    }
+    pub fn queue_inject(&mut self, hatch: u64, now: f32) {
+        let ends_at = self.inject_ends_at(hatch).max(now) + INJECT_DURATION;
+        self.inject_ends_at.insert(hatch, ends_at);
+    }
+    pub fn reset_inject(&mut self, hatch: u64, now: f32) {
+        self.inject_ends_at.insert(hatch, now);
+    }
+}
+
+impl FaxBot {
+    fn queens(&self) -> Units {
+        self.units.my.units.filter(|u| u.type_id() == UnitTypeId::Queen)
+    }
+
+    /// Injured units that are worth spending a transfuse on
+    fn is_transfuse_target(&self, unit: &Unit) -> bool {
+        let cost = self.get_unit_cost(unit.type_id());
+        !unit.is_structure()
+            && cost.minerals + cost.vespene >= 100
+            && unit.health_percentage().is_some_and(|h| h < TRANSFUSE_HEALTH_THRESHOLD)
+    }
+
+    /// Returns tags of queens that cast something this step. Inject queens keep the energy for
+    /// their next inject.
+    fn transfuse(&mut self) -> Vec<u64> {
+        let mut casters = vec![];
+        let targets = self.units.my.units.filter(|u| self.is_transfuse_target(u));
+        if targets.is_empty() {
+            return casters;
+        }
+        let inject_energy = self.energy_cost(AbilityId::EffectInjectLarva).unwrap();
+        let transfuse_energy = self.energy_cost(AbilityId::TransfusionTransfusion).unwrap();
+        for queen in self.queens().iter() {
+            let reserve = if self.state.micro.queens.assigned_hatch(queen.tag()).is_some() {
+                inject_energy
+            } else {
+                0
+            };
+            if !self.can_cast(queen, AbilityId::TransfusionTransfusion)
+                || (queen.energy().unwrap_or(0) as usize) < reserve + transfuse_energy
+            {
+                continue;
+            }
+            if let Some(target) = targets
+                .filter(|u| u.tag() != queen.tag() && u.distance(queen) <= TRANSFUSE_RANGE)
+                .min(|u| u.health().unwrap_or(0))
+            {
+                self.cast(queen, AbilityId::TransfusionTransfusion, Target::Tag(target.tag()));
+                casters.push(queen.tag());
+            }
+        }
+        casters
+    }
+
+    /// Drops assignments to dead units and gives every ready hatchery without a queen the
+    /// closest unassigned one
+    fn assign_queens(&mut self) {
+        let queens = self.queens();
+        let townhalls = self.units.my.townhalls.ready();
+        self.state
+            .micro
+            .queens
+            .retain(|q, h| queens.contains_tag(q) && townhalls.contains_tag(h));
+        for hatch in townhalls.iter() {
+            if self.state.micro.queens.queen_for(hatch.tag()).is_some() {
+                continue;
+            }
+            let free_queen = queens
+                .filter(|q| self.state.micro.queens.assigned_hatch(q.tag()).is_none())
+                .closest(hatch)
+                .map(|q| q.tag());
+            if let Some(queen) = free_queen {
+                println!("{}: Assigning queen {} to hatchery {}", self.current_iteration, queen, hatch.tag());
+                self.state.micro.queens.assign(queen, hatch.tag());
+            }
+        }
+    }
+
+    /// Injects are stacked on the hatchery, so each assigned queen injects again a little before
+    /// the previous inject runs out
+    fn inject_larva(&mut self, busy: &[u64]) -> Vec<u64> {
+        let mut casters = vec![];
+        let now = self.time;
+        for (queen, hatch) in self.state.micro.queens.assignments() {
+            let (queen, hatch) = match (self.units.my.units.get(queen), self.units.my.townhalls.get(hatch)) {
+                (Some(q), Some(h)) => (q.clone(), h.clone()),
+                _ => continue,
+            };
+            let is_injecting = queen.is_using(AbilityId::EffectInjectLarva);
+            let inject_ends_at = self.state.micro.queens.inject_ends_at(hatch.tag());
+            // Give the inject a moment to land before deciding it didn't
+            if inject_ends_at > now
+                && inject_ends_at - now < INJECT_DURATION - 2.0
+                && !is_injecting
+                && !hatch.buffs().contains(&BuffId::QueenSpawnLarvaTimer)
+            {
+                // The inject we counted on never landed, e.g. the queen got pulled away
+                println!("{}: Missed inject on hatchery {}", self.current_iteration, hatch.tag());
+                self.state.micro.queens.reset_inject(hatch.tag(), now);
+            }
+            let inject_ends_at = self.state.micro.queens.inject_ends_at(hatch.tag());
+            if busy.contains(&queen.tag())
+                || is_injecting
+                || inject_ends_at - now > INJECT_LOOKAHEAD
+                || !self.can_cast(&queen, AbilityId::EffectInjectLarva)
+            {
+                continue;
+            }
+            queen.command(AbilityId::EffectInjectLarva, Target::Tag(hatch.tag()), false);
+            self.state.micro.queens.queue_inject(hatch.tag(), now);
+            casters.push(queen.tag());
+        }
+        casters
+    }
+
+    /// Finds a spot on creep roughly `distance` away from `from`, preferring the direction of the
+    /// enemy, that isn't right next to another tumor
+    fn find_tumor_position(&self, from: Point2, distance: f32) -> Option<Point2> {
+        let tumors = self.units.my.structures.filter(|u| {
+            matches!(
+                u.type_id(),
+                UnitTypeId::CreepTumor | UnitTypeId::CreepTumorBurrowed | UnitTypeId::CreepTumorQueen
+            )
+        });
+        let forward = (self.enemy_start - from).normalize();
+        // Try straight ahead first, then fan out to the sides
+        [0.0f32, 0.5, -0.5, 1.0, -1.0, 1.5, -1.5]
+            .iter()
+            .map(|&angle| {
+                let (sin, cos) = angle.sin_cos();
+                let dir = Point2::new(forward.x * cos - forward.y * sin, forward.x * sin + forward.y * cos);
+                from + dir * distance
+            })
+            .find(|&p| {
+                self.has_creep(p)
+                    && self.is_placeable(p)
+                    && tumors.iter().closest_distance(p).is_none_or(|d| d > 4.0)
+            })
+    }
+
+    /// Free queens drop tumors whenever they can. Inject queens only do so with spare energy
+    /// beyond their next inject.
+    fn place_creep_tumors(&mut self, busy: &[u64]) {
+        let inject_energy = self.energy_cost(AbilityId::EffectInjectLarva).unwrap();
+        let tumor_energy = self.energy_cost(AbilityId::BuildCreepTumorQueen).unwrap();
+        let queens = self.queens().idle().filter(|q| {
+            let reserve = if self.state.micro.queens.assigned_hatch(q.tag()).is_some() {
+                inject_energy
+            } else {
+                0
+            };
+            !busy.contains(&q.tag()) && q.energy().unwrap_or(0) as usize >= reserve + tumor_energy
+        });
+        for queen in queens.iter() {
+            let origin = self
+                .units
+                .my
+                .townhalls
+                .closest(queen)
+                .map_or(queen.position(), |th| th.position());
+            if let Some(position) = self.find_tumor_position(origin, TUMOR_SPREAD_DISTANCE) {
+                self.cast(queen, AbilityId::BuildCreepTumorQueen, Target::Pos(position));
+            }
+        }
+        let tumors = self
+            .units
+            .my
+            .structures
+            .filter(|u| u.type_id() == UnitTypeId::CreepTumorBurrowed && !self.state.micro.spent_tumors.contains(&u.tag()));
+        let now = self.time;
+        for tumor in tumors.iter() {
+            // The cooldown starts when we first see the tumor
+            self.state
+                .micro
+                .ability_used_at
+                .entry((tumor.tag(), AbilityId::BuildCreepTumorTumor))
+                .or_insert(now);
+            if !self.can_cast(tumor, AbilityId::BuildCreepTumorTumor) {
+                continue;
+            }
+            if let Some(position) = self.find_tumor_position(tumor.position(), TUMOR_SPREAD_DISTANCE) {
+                self.cast(tumor, AbilityId::BuildCreepTumorTumor, Target::Pos(position));
+                self.state.micro.spent_tumors.insert(tumor.tag());
+            }
+        }
+    }
+
+    /// Sends the closest free queen to any base being harassed by air units without a queen nearby.
+    /// Returns tags of the queens sent.
+    fn defend_against_air_harass(&mut self, busy: &[u64]) -> Vec<u64> {
+        let mut defenders = vec![];
+        let air_threats = self
+            .units
+            .enemy
+            .units
+            .filter(|u| u.is_flying() && (u.can_attack_ground() || u.type_id() == UnitTypeId::Oracle));
+        if air_threats.is_empty() {
+            return defenders;
+        }
+        let mut queens = self.queens().filter(|q| !busy.contains(&q.tag()));
+        for base in self.state.bases.clone() {
+            if let Some(threat) = air_threats.closer(AIR_HARASS_RADIUS, base).closest(base) {
+                if !queens.closer(AIR_HARASS_RADIUS, base).is_empty() {
+                    continue;
+                }
+                // Inject queens stay home unless nothing else is available
+                let free_queens = queens.filter(|q| self.state.micro.queens.assigned_hatch(q.tag()).is_none());
+                let responder = free_queens.closest(base).or_else(|| queens.closest(base)).cloned();
+                if let Some(queen) = responder {
+                    queen.attack(Target::Pos(threat.position()), false);
+                    queens.remove(queen.tag());
+                    defenders.push(queen.tag());
+                }
+            }
+        }
+        defenders
+    }
+
+    /// Transfuse first since it saves units, then inject as the default use of energy, and only
+    /// then creep. Queens that didn't cast anything can go help with air harass, and the rest go
+    /// back to their hatchery.
+    pub fn micro_queens(&mut self) {
+        self.assign_queens();
+        let mut busy = self.transfuse();
+        busy.extend(self.inject_larva(&busy));
+        self.place_creep_tumors(&busy);
+        busy.extend(self.defend_against_air_harass(&busy));
+        self.position_queens(&busy);
+    }
+}
@@ -0,0 +1,190 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::production::{priority, ProductionKind};
+use rust_sc2::prelude::{AbilityId as A, UnitTypeId as U, UpgradeId as Up};
+use std::collections::HashMap;
+use self::ResearchFocus as F;
+
+/// Banked gas at which overlord speed stops competing with tech and units
+const OVERLORD_SPEED_VESPENE: u32 = 200;
+
+/// What part of the army an upgrade helps, used to weigh it against the current composition
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResearchFocus {
+    Ranged,
+    Melee,
+    Armor,
+    Unit(UnitTypeId),
+    Utility,
+    /// Only worth it once we have gas to spare
+    Overlords,
+    /// Zergling speed for runbys, wanted once we're on two bases whatever the army looks like
+    Runby,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ResearchStep {
+    pub upgrade: UpgradeId,
+    pub ability: AbilityId,
+    pub researcher: UnitTypeId,
+    /// Tech structure that has to exist before this can be researched
+    pub requires: Option<UnitTypeId>,
+    /// Upgrade that has to be finished first, i.e. the previous level
+    pub after: Option<UpgradeId>,
+    pub focus: ResearchFocus,
+    /// Added on top of the composition based score that orders upgrades
+    pub bonus: i32,
+}
+
+const fn step(
+    upgrade: UpgradeId,
+    ability: AbilityId,
+    researcher: UnitTypeId,
+    requires: Option<UnitTypeId>,
+    after: Option<UpgradeId>,
+    focus: ResearchFocus,
+    bonus: i32,
+) -> ResearchStep {
+    ResearchStep {
+        upgrade,
+        ability,
+        researcher,
+        requires,
+        after,
+        focus,
+        bonus,
+    }
+}
+
+pub const RESEARCH_ROADMAP: &[ResearchStep] = &[
+    step(Up::ZergMissileWeaponsLevel1, A::ResearchZergMissileWeaponsLevel1, U::EvolutionChamber, None, None, F::Ranged, 2),
+    step(Up::ZergMissileWeaponsLevel2, A::ResearchZergMissileWeaponsLevel2, U::EvolutionChamber, Some(U::Lair), Some(Up::ZergMissileWeaponsLevel1), F::Ranged, 1),
+    step(Up::ZergMissileWeaponsLevel3, A::ResearchZergMissileWeaponsLevel3, U::EvolutionChamber, Some(U::Hive), Some(Up::ZergMissileWeaponsLevel2), F::Ranged, 0),
+    step(Up::ZergMeleeWeaponsLevel1, A::ResearchZergMeleeWeaponsLevel1, U::EvolutionChamber, None, None, F::Melee, 2),
+    step(Up::ZergMeleeWeaponsLevel2, A::ResearchZergMeleeWeaponsLevel2, U::EvolutionChamber, Some(U::Lair), Some(Up::ZergMeleeWeaponsLevel1), F::Melee, 1),
+    step(Up::ZergMeleeWeaponsLevel3, A::ResearchZergMeleeWeaponsLevel3, U::EvolutionChamber, Some(U::Hive), Some(Up::ZergMeleeWeaponsLevel2), F::Melee, 0),
+    step(Up::ZergGroundArmorsLevel1, A::ResearchZergGroundArmorLevel1, U::EvolutionChamber, None, None, F::Armor, 1),
+    step(Up::ZergGroundArmorsLevel2, A::ResearchZergGroundArmorLevel2, U::EvolutionChamber, Some(U::Lair), Some(Up::ZergGroundArmorsLevel1), F::Armor, 0),
+    step(Up::ZergGroundArmorsLevel3, A::ResearchZergGroundArmorLevel3, U::EvolutionChamber, Some(U::Hive), Some(Up::ZergGroundArmorsLevel2), F::Armor, -1),
+    step(Up::Zerglingmovementspeed, A::ResearchZerglingMetabolicBoost, U::SpawningPool, None, None, F::Runby, 5),
+    step(Up::Zerglingattackspeed, A::ResearchZerglingAdrenalGlands, U::SpawningPool, Some(U::Hive), Some(Up::Zerglingmovementspeed), F::Unit(U::Zergling), 0),
+    step(Up::GlialReconstitution, A::ResearchGlialRegeneration, U::RoachWarren, Some(U::Lair), None, F::Unit(U::Roach), 4),
+    step(Up::TunnelingClaws, A::ResearchTunnelingClaws, U::RoachWarren, Some(U::Lair), Some(Up::Burrow), F::Unit(U::Roach), 1),
+    step(Up::EvolveGroovedSpines, A::ResearchGroovedSpines, U::HydraliskDen, None, None, F::Unit(U::Hydralisk), 4),
+    step(Up::EvolveMuscularAugments, A::ResearchMuscularAugments, U::HydraliskDen, None, Some(Up::EvolveGroovedSpines), F::Unit(U::Hydralisk), 3),
+    step(Up::ChitinousPlating, A::ResearchChitinousPlating, U::UltraliskCavern, None, None, F::Unit(U::Ultralisk), 4),
+    step(Up::AnabolicSynthesis, A::ResearchAnabolicSynthesis, U::UltraliskCavern, None, None, F::Unit(U::Ultralisk), 2),
+    // Utility, so it only goes ahead of combat upgrades nobody's army needs. Both are researched
+    // at a townhall, so they wait for Lair to keep the main free for queens and the Lair morph.
+    step(Up::Overlordspeed, A::ResearchPneumatizedCarapace, U::Hatchery, Some(U::Lair), None, F::Overlords, 0),
+    // Mostly for roaches to heal while burrowed, which takes Tunneling Claws and so Lair anyway
+    step(Up::Burrow, A::ResearchBurrow, U::Hatchery, Some(U::Lair), None, F::Utility, 0),
+];
+
+/// Lair and Hive can still do everything a Hatchery can
+pub fn is_researcher(researcher: UnitTypeId, unit_type: UnitTypeId) -> bool {
+    unit_type == researcher
+        || (researcher == UnitTypeId::Hatchery
+        && matches!(unit_type, UnitTypeId::Lair | UnitTypeId::Hive))
+}
+
+impl FaxBot {
+    /// Counts tech structures along with anything they morph into
+    pub fn has_tech(&self, tech: UnitTypeId) -> bool {
+        let counter = self.counter();
+        match tech {
+            UnitTypeId::Lair => counter.count(UnitTypeId::Lair) + counter.count(UnitTypeId::Hive) > 0,
+            UnitTypeId::Spire => counter.count(UnitTypeId::Spire) + counter.count(UnitTypeId::GreaterSpire) > 0,
+            _ => counter.count(tech) > 0,
+        }
+    }
+
+    /// Share of army supply made up by each unit type
+    pub fn army_composition(&self) -> HashMap<UnitTypeId, f32> {
+        let mut supply_by_type = HashMap::new();
+        let mut total = 0.0;
+        for unit in self.units.my.units.iter().filter(|u| !u.is_worker() && u.type_id() != UnitTypeId::Overlord) {
+            let supply = self.get_unit_cost(unit.type_id()).supply;
+            *supply_by_type.entry(unit.type_id()).or_insert(0.0) += supply;
+            total += supply;
+        }
+        if total > 0.0 {
+            for share in supply_by_type.values_mut() {
+                *share /= total;
+            }
+        }
+        supply_by_type
+    }
+
+    fn research_weight(&self, focus: ResearchFocus, composition: &HashMap<UnitTypeId, f32>) -> f32 {
+        let share = |types: &[UnitTypeId]| types.iter().filter_map(|t| composition.get(t)).sum::<f32>();
+        match focus {
+            ResearchFocus::Ranged => share(&[UnitTypeId::Roach, UnitTypeId::Hydralisk, UnitTypeId::Queen]),
+            ResearchFocus::Melee => share(&[UnitTypeId::Zergling, UnitTypeId::Baneling]),
+            ResearchFocus::Armor => 0.6,
+            ResearchFocus::Unit(unit_type) => share(&[unit_type]),
+            ResearchFocus::Utility => 0.3,
+            ResearchFocus::Overlords if self.vespene >= OVERLORD_SPEED_VESPENE => 0.5,
+            ResearchFocus::Overlords => 0.0,
+            ResearchFocus::Runby if self.units.my.townhalls.len() >= 2 => share(&[UnitTypeId::Zergling]).max(0.4),
+            ResearchFocus::Runby => share(&[UnitTypeId::Zergling]),
+        }
+    }
+
+    fn can_research_step(&self, step: &ResearchStep) -> bool {
+        !self.has_upgrade(step.upgrade)
+            && !self.is_ordered_upgrade(step.upgrade)
+            && step.requires.is_none_or(|t| self.has_tech(t))
+            && step.after.is_none_or(|u| self.has_upgrade(u))
+    }
+
+    /// Queues the most useful research for each idle researcher. Usefulness only orders
+    /// upgrades among themselves, they all go in at `priority::UPGRADE` so they never hold
+    /// money ahead of drones.
+    pub fn plan_research(&mut self) {
+        let composition = self.army_composition();
+        let mut candidates = RESEARCH_ROADMAP
+            .iter()
+            .filter(|step| self.can_research_step(step))
+            .filter_map(|step| {
+                let weight = self.research_weight(step.focus, &composition);
+                // Not worth it for units we don't have
+                (weight >= 0.1).then_some((step, (weight * 10.0) as i32 + step.bonus))
+            })
+            .collect::<Vec<_>>();
+        // The production queue sort is stable, so this order survives among equal priorities
+        candidates.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        let mut idle_researchers = HashMap::new();
+        for unit in self.units.my.structures.iter().filter(|u| u.is_ready() && u.orders().is_empty()) {
+            *idle_researchers.entry(unit.type_id()).or_insert(0usize) += 1;
+        }
+        for (step, _) in candidates {
+            let available = idle_researchers
+                .iter_mut()
+                .find(|(t, n)| **n > 0 && is_researcher(step.researcher, **t));
+            if let Some((_, n)) = available {
+                *n -= 1;
+                self.state.production.push(
+                    ProductionKind::Upgrade {
+                        researcher: step.researcher,
+                        upgrade: step.upgrade,
+                        ability: step.ability,
+                    },
+                    priority::UPGRADE,
+                );
+            }
+        }
+    }
+
+    /// Wants one chamber once we have an army to upgrade, and a second one once we're on Lair
+    pub fn desired_evolution_chambers(&self) -> usize {
+        if self.has_tech(UnitTypeId::Lair) && self.supply_used >= 70 {
+            2
+        } else if self.supply_used >= 44 && self.has_tech(UnitTypeId::RoachWarren) {
+            1
+        } else {
+            0
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::targeting::ENEMY_STATIC_DEFENSE;
+
+/// Zerglings sent on a runby
+const RUNBY_SIZE: usize = 8;
+/// Wait between runbys, so we don't feed lings into a mineral line that's ready for us
+const RUNBY_COOLDOWN: usize = 22 * 90;
+/// How far the path swings out to the side to get around the enemy army
+const DETOUR_DISTANCE: f32 = 20.0;
+/// Enemies within this range of the squad count as a response
+const RESPONSE_RADIUS: f32 = 10.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RunbyPhase {
+    Idle,
+    Approaching { waypoint: Point2, target: Point2 },
+    Harassing { target: Point2 },
+    Retreating,
+}
+
+/// A small group of speedlings running into enemy mineral lines, kept out of the main army
+#[derive(Debug)]
+pub struct Runby {
+    /// While idle, the new lings set aside for the next runby
+    pub members: Vec<u64>,
+    pub phase: RunbyPhase,
+    next_launch: usize,
+}
+
+impl Default for Runby {
+    fn default() -> Self {
+        Runby {
+            members: vec![],
+            phase: RunbyPhase::Idle,
+            next_launch: 0,
+        }
+    }
+}
+
+impl Runby {
+    pub fn contains(&self, tag: u64) -> bool {
+        self.members.contains(&tag)
+    }
+}
+
+impl FaxBot {
+    fn has_speedlings(&self) -> bool {
+        self.has_upgrade(UpgradeId::Zerglingmovementspeed)
+    }
+
+    /// Larvae to spend on lings so there are enough for a runby once they have speed. Each larva
+    /// makes two, and they're set aside for the runby as they hatch.
+    pub fn runby_larvae_wanted(&self) -> usize {
+        let runby = &self.state.micro.runby;
+        if !self.has_speedlings() || runby.phase != RunbyPhase::Idle {
+            return 0;
+        }
+        let reserved = self.units.my.units.find_tags(&runby.members).len();
+        let hatching = 2 * self.counter().ordered().count(UnitTypeId::Zergling);
+        (RUNBY_SIZE.saturating_sub(reserved + hatching) + 1) / 2
+    }
+
+    /// Sets a new ling aside for the next runby instead of sending it to the army, if the runby
+    /// still needs lings. Returns whether it was taken.
+    pub fn reserve_runby_ling(&mut self, tag: u64) -> bool {
+        if !self.has_speedlings() {
+            return false;
+        }
+        let runby = &mut self.state.micro.runby;
+        if runby.phase != RunbyPhase::Idle || runby.members.len() >= RUNBY_SIZE {
+            return false;
+        }
+        runby.members.push(tag);
+        true
+    }
+
+    /// The enemy mineral line furthest from where we last saw their army
+    fn runby_target(&self, enemy_army: Option<Point2>) -> Option<Point2> {
+        let mut bases = self
+            .units
+            .enemy
+            .townhalls
+            .iter()
+            .map(|th| th.position())
+            .collect::<Vec<_>>();
+        if bases.is_empty() {
+            bases.push(self.enemy_start);
+        }
+        let furthest = match enemy_army {
+            Some(army) => bases
+                .into_iter()
+                .max_by_key(|b| float_ord::FloatOrd(b.distance(army))),
+            None => bases.into_iter().min_by_key(|b| float_ord::FloatOrd(b.distance(self.start_location))),
+        }?;
+        Some(self.mineral_line_defense_position(furthest))
+    }
+
+    /// A point off to the side of the straight path, on whichever side is further from the army
+    fn runby_waypoint(&self, from: Point2, target: Point2, enemy_army: Option<Point2>) -> Point2 {
+        let mid = (from + target) / 2.0;
+        let dir = (target - from).normalize();
+        let side = Point2::new(-dir.y, dir.x);
+        [DETOUR_DISTANCE, -DETOUR_DISTANCE, DETOUR_DISTANCE / 2.0, -DETOUR_DISTANCE / 2.0]
+            .iter()
+            .map(|&offset| mid + side * offset)
+            .filter(|&p| self.is_pathable(p))
+            .max_by_key(|&p| float_ord::FloatOrd(enemy_army.map_or(0.0, |a| p.distance(a))))
+            .unwrap_or(mid)
+    }
+
+    fn enemy_army_center(&self) -> Option<Point2> {
+        let army = self
+            .state
+            .get_recent_enemy_spotted_information(self.current_iteration)
+            .into_iter()
+            .filter(|&(_, t)| !t.is_worker() && !self.is_structure_type(t))
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>();
+        if army.is_empty() {
+            return None;
+        }
+        Some(army.iter().fold(Point2::new(0.0, 0.0), |acc, &p| acc + p) / army.len() as f32)
+    }
+
+    fn launch_runby(&mut self, lings: &Units) {
+        if lings.len() < RUNBY_SIZE || self.state.is_under_attack {
+            return;
+        }
+        let enemy_army = self.enemy_army_center();
+        let target = match self.runby_target(enemy_army) {
+            Some(target) => target,
+            None => return,
+        };
+        let members = lings.tags().copied().collect::<Vec<_>>();
+        let from = lings.center().unwrap_or(self.start_location);
+        let waypoint = self.runby_waypoint(from, target, enemy_army);
+        println!(
+            "{}: Sending {} lings on a runby to {:?} via {:?}",
+            self.current_iteration,
+            members.len(),
+            target,
+            waypoint
+        );
+        self.state.micro.runby.members = members;
+        self.state.micro.runby.phase = RunbyPhase::Approaching { waypoint, target };
+    }
+
+    /// Whether static defense or enough of the army has shown up to fight the squad
+    fn runby_is_answered(&self, squad: &Units) -> bool {
+        let center = match squad.center() {
+            Some(center) => center,
+            None => return true,
+        };
+        let enemies = self.units.enemy.all.closer(RESPONSE_RADIUS, center);
+        let has_static_defense = enemies.iter().any(|e| ENEMY_STATIC_DEFENSE.contains(&e.type_id()));
+        let defenders = enemies
+            .filter(|e| !e.is_worker() && !e.is_structure() && e.can_attack_ground())
+            .len();
+        has_static_defense || defenders * 2 >= squad.len()
+    }
+
+    /// Speedlings go around the enemy army to a mineral line, kill workers until someone answers,
+    /// and then run home and rejoin the army
+    pub fn micro_runby(&mut self) {
+        if !self.has_speedlings() {
+            return;
+        }
+        let iteration = self.current_iteration;
+        let runby = &self.state.micro.runby;
+        let squad = self.units.my.units.find_tags(&runby.members);
+        let phase = runby.phase;
+        if phase == RunbyPhase::Idle {
+            // Reserved lings that died make room for new ones
+            self.state.micro.runby.members = squad.tags().copied().collect();
+            if iteration >= self.state.micro.runby.next_launch {
+                self.launch_runby(&squad);
+            }
+            return;
+        }
+        let is_answered = phase != RunbyPhase::Retreating && (squad.len() < 2 || self.runby_is_answered(&squad));
+        if is_answered {
+            println!("{}: Runby was answered, retreating", iteration);
+            self.state.micro.runby.phase = RunbyPhase::Retreating;
+        }
+        match self.state.micro.runby.phase {
+            RunbyPhase::Approaching { waypoint, target } => {
+                if squad.center().is_some_and(|c| c.distance(waypoint) < 4.0) {
+                    self.state.micro.runby.phase = RunbyPhase::Harassing { target };
+                } else {
+                    for ling in squad.iter() {
+                        ling.move_to(Target::Pos(waypoint), false);
+                    }
+                }
+            }
+            RunbyPhase::Harassing { target } => {
+                let workers = self.units.enemy.workers.clone();
+                for ling in squad.iter() {
+                    match workers.closer(RESPONSE_RADIUS, ling).closest(ling) {
+                        Some(worker) => ling.attack(Target::Tag(worker.tag()), false),
+                        None => ling.attack(Target::Pos(target), false),
+                    }
+                }
+            }
+            RunbyPhase::Retreating => {
+                let home = self.start_location;
+                if squad.center().is_none_or(|c| c.distance(home) < 15.0) {
+                    let runby = &mut self.state.micro.runby;
+                    runby.members.clear();
+                    runby.phase = RunbyPhase::Idle;
+                    runby.next_launch = iteration + RUNBY_COOLDOWN;
+                } else {
+                    for ling in squad.iter() {
+                        ling.move_to(Target::Pos(home), false);
+                    }
+                }
+            }
+            RunbyPhase::Idle => (),
+        }
+    }
+}
@@ -45,17 +45,49 @@ pub struct BuildOrderInfo {
     pub first_hatch_supply: u32,
 }
 
+/// Things we've seen that call for a defensive response. These latch once seen.
+#[derive(Debug, Default)]
+pub struct ThreatInfo {
+    pub early_aggression: bool,
+    pub air_units_seen: bool,
+    pub cloaked_units_seen: bool,
+    /// Cloaked or burrowed units that can hurt us, unlike observers
+    pub cloaked_attackers_seen: bool,
+    pub air_tech_seen: bool,
+    pub cloak_tech_seen: bool,
+}
+
+impl ThreatInfo {
+    /// Air harass and cloaked units that kill workers, so a scouting observer doesn't count
+    pub fn wants_spores(&self) -> bool {
+        self.air_units_seen || self.air_tech_seen || self.cloaked_attackers_seen || self.cloak_tech_seen
+    }
+    /// Overseers go after any cloaked unit
+    pub fn wants_detection(&self) -> bool {
+        self.cloaked_units_seen || self.cloak_tech_seen
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct BotState {
     pub bases: Vec<Point2>,
     pub build_order: BuildOrderInfo,
     pub expansion_order: Vec<rust_sc2::bot::Expansion>,
-    pub peak_army: usize,
     pub desired_workers: usize,
     pub desired_gasses: usize,
+    pub desired_gas_workers: usize,
+    /// How many halves of the gas drones are off gas while we bank it, see `gas_halves_pulled`
+    pub gas_halves_pulled: usize,
+    pub gas_workers_updated_at: f32,
     pub desired_bases: usize,
     pub is_under_attack: bool,
+    pub threats: ThreatInfo,
+    pub attack: crate::attack::AttackState,
     pub micro: crate::micro::MicroState,
+    pub production: crate::production::ProductionQueue,
+    pub budget: crate::budget::Budget,
+    pub expansion_claims: crate::construction::ExpansionClaims,
+    pub construction: crate::construction::Construction,
     pub map_info: crate::map::MapInfo,
     pub my_structures: ObjectPermanence<()>,
     pub enemy_units: ObjectPermanence<UnitTypeId>,
@@ -93,11 +125,20 @@ impl BotState {
     }
     pub fn register_unit_created(&mut self, unit: &Unit, iteration: usize) {
         if unit.type_id() == UnitTypeId::Drone {
-            self.micro.drone_last_seen.insert(unit.tag(), iteration);
+            self.micro.workers.registry.register(unit.tag(), unit.position(), iteration);
         }
     }
     pub fn register_unit_destroyed(&mut self, tag: u64) {
         self.enemy_units.map.remove(&tag);
+        self.micro.workers.remove(tag);
+        self.micro.workers.registry.release_from_gas(tag);
+        self.micro.ability_used_at.retain(|&(t, _), _| t != tag);
+        self.micro.spent_tumors.remove(&tag);
+        self.micro.queens.forget(tag);
+        self.micro.reinforcements.remove(tag);
+        if let Some(claim) = self.expansion_claims.release_drone(tag) {
+            println!("Drone {} expanding to {:?} died", tag, claim.location);
+        }
     }
 
     pub fn determine_build_order(&mut self, runtime_options: &RuntimeOptions) {
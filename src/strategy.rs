@@ -40,6 +40,60 @@ impl FaxBot {
         });
         threatening_units.count()
     }
+    fn update_threats(&mut self, iteration: usize) {
+        // Starports and tech labs are in every Terran build for medivacs, so they don't count
+        let air_tech = [UnitTypeId::Stargate, UnitTypeId::FusionCore, UnitTypeId::Spire];
+        // Flyers that hurt mineral lines, unlike medivacs, vikings and scouts
+        let air_threats = [
+            UnitTypeId::Banshee,
+            UnitTypeId::Liberator,
+            UnitTypeId::LiberatorAG,
+            UnitTypeId::Battlecruiser,
+            UnitTypeId::Oracle,
+            UnitTypeId::VoidRay,
+            UnitTypeId::Carrier,
+            UnitTypeId::Tempest,
+            UnitTypeId::Mutalisk,
+            UnitTypeId::BroodLord,
+        ];
+        // Anything that means cloaked or burrowed units are on the way
+        let cloak_tech = [
+            UnitTypeId::DarkShrine,
+            UnitTypeId::DarkTemplar,
+            UnitTypeId::Banshee,
+            UnitTypeId::GhostAcademy,
+            UnitTypeId::LurkerDenMP,
+        ];
+        let enemies = self.units.enemy.all.clone();
+        let is_early_aggression = self.state.is_under_attack && self.time < 60.0 * 5.0;
+        let threats = &mut self.state.threats;
+        if is_early_aggression && !threats.early_aggression {
+            println!("{}: Early aggression detected", iteration);
+            threats.early_aggression = true;
+        }
+        for unit in enemies.iter() {
+            if air_threats.contains(&unit.type_id()) && !threats.air_units_seen {
+                println!("{}: Enemy air units seen ({:?})", iteration, unit.type_id());
+                threats.air_units_seen = true;
+            }
+            if (unit.is_cloaked() || unit.is_burrowed()) && !threats.cloaked_units_seen {
+                println!("{}: Enemy cloaked units seen ({:?})", iteration, unit.type_id());
+                threats.cloaked_units_seen = true;
+            }
+            if (unit.is_cloaked() || unit.is_burrowed()) && unit.can_attack() && !threats.cloaked_attackers_seen {
+                println!("{}: Enemy cloaked attackers seen ({:?})", iteration, unit.type_id());
+                threats.cloaked_attackers_seen = true;
+            }
+            if cloak_tech.contains(&unit.type_id()) && !threats.cloak_tech_seen {
+                println!("{}: Enemy cloak tech seen ({:?})", iteration, unit.type_id());
+                threats.cloak_tech_seen = true;
+            }
+            if air_tech.contains(&unit.type_id()) && !threats.air_tech_seen {
+                println!("{}: Enemy air tech seen ({:?})", iteration, unit.type_id());
+                threats.air_tech_seen = true;
+            }
+        }
+    }
     pub fn determine_state_for_tick(&mut self, _iteration: usize) {
         self.state
             .update_my_recent_structure_positions(&self.units.my.structures.clone(), _iteration);
@@ -51,6 +105,9 @@ impl FaxBot {
             println!("Under attack? {}", is_under_attack);
         }
         self.state.is_under_attack = is_under_attack;
+        self.update_threats(_iteration);
+        self.update_expansion_claims();
+        self.update_construction_jobs();
         {
             let mut expansions = self.expansions.clone();
             let mut unaccounted_mineral_workers =
@@ -89,6 +146,8 @@ impl FaxBot {
             .iter()
             .map(|th| th.position())
             .collect();
+        self.update_defense_positions();
+        self.update_army_mode();
         // println!("\n\n\nState: {:?}", self.state);
     }
 }
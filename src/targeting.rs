@@ -0,0 +1,215 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use rust_sc2::game_data::Attribute;
+use rust_sc2::prelude::UnitTypeId as U;
+
+/// Enemy structures that shoot at ground units
+pub const ENEMY_STATIC_DEFENSE: &[UnitTypeId] = &[U::PhotonCannon, U::Bunker, U::PlanetaryFortress, U::SpineCrawler];
+const ENEMY_TOWNHALLS: &[UnitTypeId] = &[
+    U::Nexus,
+    U::CommandCenter,
+    U::OrbitalCommand,
+    U::PlanetaryFortress,
+    U::Hatchery,
+    U::Lair,
+    U::Hive,
+];
+const ENEMY_PRODUCTION: &[UnitTypeId] = &[
+    U::Gateway,
+    U::WarpGate,
+    U::RoboticsFacility,
+    U::Stargate,
+    U::Barracks,
+    U::Factory,
+    U::Starport,
+    U::SpawningPool,
+    U::RoachWarren,
+    U::Spire,
+];
+/// Our army supply has to be this many times theirs before we go for their army
+const FAVOURED_RATIO: f32 = 1.3;
+/// Enemy army and static defense within this range of a target make it risky
+const DEFENSE_RADIUS: f32 = 12.0;
+/// Risk of one static defense structure, in army supply
+const STATIC_DEFENSE_RISK: f32 = 4.0;
+/// Defense below this, in army supply, counts as undefended
+const UNDEFENDED_THRESHOLD: f32 = 2.0;
+/// Score lost per unit of ground distance, so closer targets win ties
+const DISTANCE_PENALTY: f32 = 0.05;
+/// Paths end this far out from a target towards our army, since structures aren't pathable
+const PATH_OFFSET: f32 = 4.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AttackTargetKind {
+    UndefendedBase,
+    Army,
+    Base,
+    Production,
+    Air,
+}
+
+impl AttackTargetKind {
+    fn value(self) -> f32 {
+        match self {
+            AttackTargetKind::UndefendedBase => 12.0,
+            AttackTargetKind::Army => 10.0,
+            AttackTargetKind::Base => 8.0,
+            AttackTargetKind::Production => 6.0,
+            AttackTargetKind::Air => 4.0,
+        }
+    }
+}
+
+impl FaxBot {
+    pub fn is_structure_type(&self, unit_type: UnitTypeId) -> bool {
+        self.game_data
+            .units
+            .get(&unit_type)
+            .is_some_and(|d| d.attributes.contains(&Attribute::Structure))
+    }
+
+    /// Every enemy structure we've seen and haven't seen die
+    fn known_enemy_structures(&self) -> Vec<(Point2, UnitTypeId)> {
+        self.state
+            .enemy_units
+            .map
+            .values()
+            .filter(|(_, t)| self.is_structure_type(*t))
+            .map(|(o, t)| (o.position, *t))
+            .collect()
+    }
+
+    /// Recently seen enemy army units, leaving out workers and scouts
+    pub fn recent_enemy_army(&self) -> Vec<(Point2, UnitTypeId)> {
+        self.state
+            .get_recent_enemy_spotted_information(self.current_iteration)
+            .into_iter()
+            .filter(|&(_, t)| {
+                !t.is_worker()
+                    && !self.is_structure_type(t)
+                    && !matches!(t, U::Overlord | U::OverlordTransport | U::Overseer | U::Observer)
+            })
+            .collect()
+    }
+
+    pub fn army_supply(&self) -> f32 {
+        self.units
+            .my
+            .units
+            .iter()
+            .filter(|u| !u.is_worker() && u.can_attack() && u.type_id() != UnitTypeId::Queen)
+            .map(|u| self.get_unit_cost(u.type_id()).supply)
+            .sum()
+    }
+
+    /// Enemy army supply plus static defense close to `position`
+    fn defense_near(&self, position: Point2, army: &[(Point2, UnitTypeId)], structures: &[(Point2, UnitTypeId)]) -> f32 {
+        let army_supply = army
+            .iter()
+            .filter(|(p, _)| p.distance(position) <= DEFENSE_RADIUS)
+            .map(|(_, t)| self.get_unit_cost(*t).supply)
+            .sum::<f32>();
+        let static_defense = structures
+            .iter()
+            .filter(|(p, t)| ENEMY_STATIC_DEFENSE.contains(t) && p.distance(position) <= DEFENSE_RADIUS)
+            .count();
+        army_supply + static_defense as f32 * STATIC_DEFENSE_RISK
+    }
+
+    fn has_anti_air(&self) -> bool {
+        self.units.my.units.iter().any(|u| u.can_attack_air() && u.type_id() != UnitTypeId::Queen)
+    }
+
+    /// Candidate targets with their kind, before risk and distance are taken into account
+    fn attack_target_candidates(&self) -> Vec<(Point2, AttackTargetKind)> {
+        let structures = self.known_enemy_structures();
+        let army = self.recent_enemy_army();
+        let mut candidates = vec![];
+        for &(position, unit_type) in structures.iter() {
+            if ENEMY_TOWNHALLS.contains(&unit_type) {
+                let kind = if self.defense_near(position, &army, &structures) < UNDEFENDED_THRESHOLD {
+                    AttackTargetKind::UndefendedBase
+                } else {
+                    AttackTargetKind::Base
+                };
+                candidates.push((position, kind));
+            } else if ENEMY_PRODUCTION.contains(&unit_type) {
+                candidates.push((position, AttackTargetKind::Production));
+            }
+        }
+        let enemy_supply = army.iter().map(|(_, t)| self.get_unit_cost(*t).supply).sum::<f32>();
+        let ground_army = self.units.enemy.units.filter(|u| !u.is_flying() && !u.is_worker());
+        if self.army_supply() >= enemy_supply * FAVOURED_RATIO {
+            if let Some(center) = ground_army.center() {
+                candidates.push((center, AttackTargetKind::Army));
+            }
+        }
+        // Only flying things left to kill, which only matters if we can shoot up
+        if candidates.is_empty() && ground_army.is_empty() && self.has_anti_air() {
+            if let Some(flyer) = self.units.enemy.all.filter(|u| u.is_flying()).closest(self.start_location) {
+                candidates.push((flyer.position(), AttackTargetKind::Air));
+            }
+        }
+        candidates
+    }
+
+    /// The best place to attack: targets are scored by what they're worth, minus the enemy
+    /// defense around them and the ground distance from our army
+    pub fn choose_attack_target(&self) -> Point2 {
+        let structures = self.known_enemy_structures();
+        let army = self.recent_enemy_army();
+        let candidates = self.attack_target_candidates();
+        // A real unit of the army that will follow the target, so the path starts somewhere
+        // pathable, closest to the middle of that army
+        let ground_army = self.attack_army().filter(|u| !u.is_flying());
+        let walker = ground_army.center().and_then(|center| ground_army.closest(center));
+        let from = walker.map_or(self.start_location, |u| u.position());
+        let distances = match walker {
+            Some(walker) if !candidates.is_empty() => self
+                .query_pathing(
+                    candidates
+                        .iter()
+                        .map(|&(p, _)| (Target::Tag(walker.tag()), p.towards(from, PATH_OFFSET)))
+                        .collect(),
+                )
+                .unwrap_or_default(),
+            _ => vec![],
+        };
+        let best = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, &(position, kind))| {
+                let distance = match distances.get(i) {
+                    Some(&Some(distance)) => distance + PATH_OFFSET,
+                    _ => from.distance(position),
+                };
+                let risk = if kind == AttackTargetKind::Army {
+                    0.0
+                } else {
+                    self.defense_near(position, &army, &structures)
+                };
+                (position, kind, kind.value() - risk - distance * DISTANCE_PENALTY)
+            })
+            .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+        if let Some((position, kind, _)) = best {
+            let previous = self.state.attack.target.map(|(p, _)| p);
+            if previous.is_none_or(|p| p.distance(position) > 1.0) {
+                println!("{}: Attack target {:?} at {:?}", self.current_iteration, kind, position);
+            }
+            return position;
+        }
+        // Nothing known, go look for them
+        for point in self
+            .state
+            .micro
+            .enemy_base_locations_by_expansion_order
+            .iter()
+        {
+            if self.is_hidden(*point) {
+                return *point;
+            }
+        }
+        self.state.map_info.get_random_point()
+    }
+}
@@ -0,0 +1,149 @@
+use rust_sc2::prelude::*;
+
+use crate::bot::FaxBot;
+use crate::production::{priority, ProductionKind};
+use rust_sc2::prelude::UnitTypeId as U;
+use std::collections::HashMap;
+
+/// Supply at which we start teching past Lair, unless the game goes on long enough anyway
+const LATE_GAME_SUPPLY: u32 = 110;
+const LATE_GAME_TIME: f32 = 60.0 * 10.0;
+const LATE_GAME_BASES: usize = 3;
+const TECH_RESERVATION_LIFETIME: usize = 22 * 45;
+
+/// What the enemy's army is mostly made of, as far as our tech choice goes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EnemyStyle {
+    Bio,
+    Mech,
+    Sky,
+    Gateway,
+    Zerg,
+}
+
+/// Enemy units that decide the style, and which style they belong to
+const STYLE_UNITS: &[(UnitTypeId, EnemyStyle)] = &[
+    (U::Marine, EnemyStyle::Bio),
+    (U::Marauder, EnemyStyle::Bio),
+    (U::Medivac, EnemyStyle::Bio),
+    (U::Ghost, EnemyStyle::Bio),
+    (U::SiegeTank, EnemyStyle::Mech),
+    (U::SiegeTankSieged, EnemyStyle::Mech),
+    (U::Thor, EnemyStyle::Mech),
+    (U::Hellion, EnemyStyle::Mech),
+    (U::HellionTank, EnemyStyle::Mech),
+    (U::Cyclone, EnemyStyle::Mech),
+    (U::WidowMine, EnemyStyle::Mech),
+    (U::Battlecruiser, EnemyStyle::Sky),
+    (U::Liberator, EnemyStyle::Sky),
+    (U::LiberatorAG, EnemyStyle::Sky),
+    (U::Carrier, EnemyStyle::Sky),
+    (U::Tempest, EnemyStyle::Sky),
+    (U::VoidRay, EnemyStyle::Sky),
+    (U::Phoenix, EnemyStyle::Sky),
+    (U::Zealot, EnemyStyle::Gateway),
+    (U::Stalker, EnemyStyle::Gateway),
+    (U::Adept, EnemyStyle::Gateway),
+    (U::Immortal, EnemyStyle::Gateway),
+    (U::Colossus, EnemyStyle::Gateway),
+    (U::Archon, EnemyStyle::Gateway),
+];
+
+/// Tech structures to get, in order, against each style. Hive comes from the Infestation Pit,
+/// and Greater Spire from the Spire.
+const TECH_PATHS: &[(EnemyStyle, &[UnitTypeId])] = &[
+    (EnemyStyle::Bio, &[U::LurkerDenMP, U::InfestationPit, U::Hive, U::UltraliskCavern]),
+    (EnemyStyle::Mech, &[U::Spire, U::InfestationPit, U::Hive, U::GreaterSpire]),
+    (EnemyStyle::Sky, &[U::Spire, U::InfestationPit, U::Hive]),
+    (EnemyStyle::Gateway, &[U::LurkerDenMP, U::InfestationPit, U::Hive, U::UltraliskCavern]),
+    (EnemyStyle::Zerg, &[U::LurkerDenMP, U::Spire, U::InfestationPit, U::Hive, U::UltraliskCavern]),
+];
+
+/// Tech that has to be finished before the structure can be started
+fn tech_requirement(unit_type: UnitTypeId) -> UnitTypeId {
+    match unit_type {
+        U::LurkerDenMP => U::HydraliskDen,
+        U::Hive => U::InfestationPit,
+        U::UltraliskCavern | U::GreaterSpire => U::Hive,
+        _ => U::Lair,
+    }
+}
+
+impl FaxBot {
+    /// The style with the most supply among enemy units we know of. Before we've seen any, a
+    /// guess from their race.
+    fn enemy_style(&self) -> EnemyStyle {
+        let mut supply_by_style = HashMap::new();
+        for (_, unit_type) in self.state.enemy_units.map.values() {
+            if let Some(&(_, style)) = STYLE_UNITS.iter().find(|(t, _)| t == unit_type) {
+                *supply_by_style.entry(style).or_insert(0.0) += self.get_unit_cost(*unit_type).supply;
+            }
+        }
+        let seen = supply_by_style
+            .into_iter()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(style, _)| style);
+        match (seen, self.enemy_race) {
+            (Some(style), _) => style,
+            (None, Race::Terran) => EnemyStyle::Bio,
+            (None, Race::Zerg) => EnemyStyle::Zerg,
+            (None, _) => EnemyStyle::Gateway,
+        }
+    }
+
+    fn has_or_started(&self, unit_type: UnitTypeId) -> bool {
+        self.has_tech(unit_type) || self.counter().ordered().count(unit_type) > 0
+    }
+
+    fn is_late_game(&self) -> bool {
+        self.has_tech(U::Lair)
+            && self.units.my.townhalls.len() >= LATE_GAME_BASES
+            && (self.supply_used >= LATE_GAME_SUPPLY || self.time >= LATE_GAME_TIME)
+    }
+
+    /// Starts the next structure on the tech path for the enemy's style, one at a time
+    pub fn plan_late_game_tech(&mut self, location: Point2) {
+        if !self.is_late_game() {
+            return;
+        }
+        let style = self.enemy_style();
+        let path = TECH_PATHS.iter().find(|(s, _)| *s == style).map_or(&[][..], |(_, p)| *p);
+        let next = match path.iter().find(|&&t| !self.has_or_started(t)) {
+            Some(&next) => next,
+            None => return,
+        };
+        if !self.has_tech(tech_requirement(next)) {
+            return;
+        }
+        if self.current_iteration % (22 * 30) == 0 {
+            println!("{}: Teching to {:?} against {:?}", self.current_iteration, next, style);
+        }
+        let (kind, priority) = match next {
+            U::Hive => (
+                ProductionKind::Morph {
+                    from: U::Lair,
+                    to: U::Hive,
+                    ability: AbilityId::UpgradeToHiveHive,
+                },
+                priority::TECH_MORPH,
+            ),
+            U::GreaterSpire => (
+                ProductionKind::Morph {
+                    from: U::Spire,
+                    to: U::GreaterSpire,
+                    ability: AbilityId::UpgradeToGreaterSpireGreaterSpire,
+                },
+                priority::TECH_MORPH,
+            ),
+            _ => (
+                ProductionKind::Structure {
+                    unit_type: next,
+                    location,
+                    exact: false,
+                },
+                priority::TECH_STRUCTURE,
+            ),
+        };
+        self.plan_reserved(kind, priority, TECH_RESERVATION_LIFETIME);
+    }
+}
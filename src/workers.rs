@@ -0,0 +1,562 @@
+use rust_sc2::prelude::*;
+
+use std::collections::HashMap;
+
+/// Drones a mineral patch can take before they start queuing for it
+const PATCH_SATURATION: usize = 2;
+/// Close patches are short enough trips that a third drone still adds income
+const CLOSE_PATCH_OVERSATURATION: usize = 3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ResourceType {
+    Gas,
+    Mineral,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum DroneTask {
+    #[default]
+    Idle,
+    Construct {
+        queued_at: usize,
+    },
+    Gather {
+        resource_type: ResourceType,
+        resource_tag: u64,
+        hatch: u64,
+    },
+    /// Pulled off mining to fight a worker or cannon rush
+    Defend {
+        since: usize,
+    },
+}
+
+impl DroneTask {
+    pub fn gathered_resource(&self) -> Option<u64> {
+        match self {
+            DroneTask::Gather { resource_tag, .. } => Some(*resource_tag),
+            _ => None,
+        }
+    }
+    pub fn is_gathering_at(&self, base: u64, wanted: ResourceType) -> bool {
+        matches!(self, DroneTask::Gather { hatch, resource_type, .. } if *hatch == base && *resource_type == wanted)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WorkerPresence {
+    Visible,
+    /// The API doesn't show us workers while they're inside a gas building
+    InsideGas { extractor: u64 },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct WorkerRecord {
+    pub last_seen: usize,
+    pub last_position: Point2,
+    pub presence: WorkerPresence,
+}
+
+/// Every drone we own, including the ones we can't currently see because they're inside an
+/// extractor. Drones only leave the registry when they die or morph into something else.
+#[derive(Debug, Default)]
+pub struct WorkerRegistry {
+    workers: HashMap<u64, WorkerRecord>,
+}
+
+impl WorkerRegistry {
+    pub fn register(&mut self, tag: u64, position: Point2, iteration: usize) {
+        self.workers.entry(tag).or_insert(WorkerRecord {
+            last_seen: iteration,
+            last_position: position,
+            presence: WorkerPresence::Visible,
+        });
+    }
+    pub fn remove(&mut self, tag: u64) -> bool {
+        self.workers.remove(&tag).is_some()
+    }
+    pub fn contains(&self, tag: u64) -> bool {
+        self.workers.contains_key(&tag)
+    }
+    pub fn enter_gas(&mut self, tag: u64, extractor: u64) {
+        if let Some(record) = self.workers.get_mut(&tag) {
+            record.presence = WorkerPresence::InsideGas { extractor };
+        }
+    }
+    /// Workers inside a gas building that's gone come back out, so look for them again
+    pub fn release_from_gas(&mut self, extractor: u64) {
+        for record in self.workers.values_mut() {
+            match record.presence {
+                WorkerPresence::InsideGas { extractor: e } if e == extractor => {
+                    record.presence = WorkerPresence::Visible;
+                }
+                _ => (),
+            }
+        }
+    }
+    /// Updates the registry with the workers we can see this step, and returns the ones that
+    /// were visible last time but have disappeared since
+    pub fn observe(&mut self, visible: &[(u64, Point2)], iteration: usize) -> Vec<u64> {
+        for &(tag, position) in visible {
+            self.register(tag, position, iteration);
+            let record = self.workers.get_mut(&tag).unwrap();
+            record.last_seen = iteration;
+            record.last_position = position;
+            record.presence = WorkerPresence::Visible;
+        }
+        self.workers
+            .iter()
+            .filter(|(_, r)| r.presence == WorkerPresence::Visible && r.last_seen != iteration)
+            .map(|(&tag, _)| tag)
+            .collect()
+    }
+}
+
+/// Owns every drone's task, and the registry that knows which drones are still alive
+#[derive(Debug, Default)]
+pub struct WorkerManager {
+    tasks: HashMap<u64, DroneTask>,
+    pub registry: WorkerRegistry,
+}
+
+impl WorkerManager {
+    pub fn task(&self, tag: u64) -> Option<DroneTask> {
+        self.tasks.get(&tag).copied()
+    }
+    /// Starts managing a drone we haven't seen before. Returns whether it was new.
+    pub fn add(&mut self, tag: u64) -> bool {
+        if self.tasks.contains_key(&tag) {
+            return false;
+        }
+        self.tasks.insert(tag, DroneTask::Idle);
+        true
+    }
+    pub fn assign(&mut self, tag: u64, task: DroneTask) {
+        self.tasks.insert(tag, task);
+    }
+    pub fn assign_gather(&mut self, tag: u64, hatch: u64, resource_type: ResourceType, resource_tag: u64) {
+        self.assign(tag, DroneTask::Gather { resource_type, resource_tag, hatch });
+    }
+    pub fn reserve_for_building(&mut self, tag: u64, iteration: usize) {
+        self.assign(tag, DroneTask::Construct { queued_at: iteration });
+    }
+    /// Sends a drone back to the idle pool
+    pub fn release(&mut self, tag: u64) {
+        if let Some(task) = self.tasks.get_mut(&tag) {
+            *task = DroneTask::Idle;
+        }
+    }
+    /// Forgets a drone entirely, because it died or morphed into something
+    pub fn remove(&mut self, tag: u64) {
+        self.tasks.remove(&tag);
+        self.registry.remove(tag);
+    }
+    /// Sorts out drones that disappeared from view. Only a `UnitDestroyed` event says a drone
+    /// died, so gas drones whose extractor still stands are inside it, however far from it they
+    /// were last seen. Any other drone has morphed into a building.
+    pub fn handle_vanished(&mut self, vanished: &[u64], extractors: &[u64]) {
+        for &tag in vanished {
+            match self.task(tag) {
+                Some(DroneTask::Gather { resource_type: ResourceType::Gas, resource_tag, .. })
+                    if extractors.contains(&resource_tag) =>
+                {
+                    self.registry.enter_gas(tag, resource_tag)
+                }
+                _ => self.remove(tag),
+            }
+        }
+    }
+    /// Drops tasks for drones the registry no longer knows about
+    pub fn retain_registered(&mut self) {
+        let registry = &self.registry;
+        self.tasks.retain(|&tag, _| registry.contains(tag));
+    }
+    pub fn tags_with<F: Fn(&DroneTask) -> bool>(&self, f: F) -> Vec<u64> {
+        self.tasks
+            .iter()
+            .filter(|(_, task)| f(task))
+            .map(|(&tag, _)| tag)
+            .collect()
+    }
+    pub fn idle(&self) -> Vec<u64> {
+        self.tags_with(|task| *task == DroneTask::Idle)
+    }
+    pub fn on_resource(&self, resource_tag: u64) -> Vec<u64> {
+        self.tags_with(|task| task.gathered_resource() == Some(resource_tag))
+    }
+    pub fn count_at_base(&self, base: u64, resource_type: ResourceType) -> usize {
+        self.tasks
+            .values()
+            .filter(|task| task.is_gathering_at(base, resource_type))
+            .count()
+    }
+    pub fn count_gathering(&self, resource_type: ResourceType) -> usize {
+        self.tasks
+            .values()
+            .filter(|task| matches!(task, DroneTask::Gather { resource_type: r, .. } if *r == resource_type))
+            .count()
+    }
+    /// Exact number of drones working each gas building, including the ones inside it
+    pub fn gas_workers_by_extractor(&self) -> HashMap<u64, usize> {
+        let mut counts = HashMap::new();
+        for (&tag, task) in self.tasks.iter() {
+            if let DroneTask::Gather { resource_type: ResourceType::Gas, resource_tag, .. } = task {
+                if self.registry.contains(tag) {
+                    *counts.entry(*resource_tag).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MineralPatch {
+    pub tag: u64,
+    pub hatch: u64,
+    pub position: Point2,
+    /// Distance from the townhall the patch is mined to
+    pub distance: f32,
+    pub close: bool,
+}
+
+/// Marks the nearer half of each base's patches as close ones
+pub fn mark_close_patches(patches: &mut [MineralPatch]) {
+    let mut by_hatch: HashMap<u64, Vec<f32>> = HashMap::new();
+    for patch in patches.iter() {
+        by_hatch.entry(patch.hatch).or_default().push(patch.distance);
+    }
+    for distances in by_hatch.values_mut() {
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    }
+    for patch in patches.iter_mut() {
+        let distances = &by_hatch[&patch.hatch];
+        let rank = distances.iter().filter(|&&d| d < patch.distance).count();
+        patch.close = rank < (distances.len() + 1) / 2;
+    }
+}
+
+/// A base's mineral line, and the mineral drones that are there or idle closest to it
+#[derive(Debug, Clone)]
+pub struct MineralBase {
+    pub hatch: u64,
+    pub position: Point2,
+    pub patches: usize,
+    pub close_patches: usize,
+    pub workers: usize,
+}
+
+impl MineralBase {
+    fn saturation(&self) -> usize {
+        PATCH_SATURATION * self.patches
+    }
+    fn oversaturation(&self) -> usize {
+        self.saturation() + (CLOSE_PATCH_OVERSATURATION - PATCH_SATURATION) * self.close_patches
+    }
+}
+
+/// How many mineral drones each base should get when `workers` drones mine minerals. Bases keep
+/// their own drones up to two per patch, and drones past that move to the closest base with room.
+/// Only once every base is saturated do close patches take a third, preferably at the base the
+/// drone is already at.
+pub fn mineral_shares(bases: &[MineralBase], workers: usize) -> HashMap<u64, usize> {
+    let mut shares = bases
+        .iter()
+        .map(|b| (b.hatch, b.workers.min(b.saturation())))
+        .collect::<HashMap<_, _>>();
+    // Fewer drones mine minerals than are at the bases, e.g. because gas needs some, so take them
+    // from the fullest bases
+    while shares.values().sum::<usize>() > workers {
+        let fullest = bases.iter().max_by_key(|b| (shares[&b.hatch], b.hatch)).unwrap();
+        *shares.get_mut(&fullest.hatch).unwrap() -= 1;
+    }
+    let mut left = workers - shares.values().sum::<usize>();
+    let mut surplus = bases
+        .iter()
+        .map(|b| b.workers.saturating_sub(shares[&b.hatch]))
+        .collect::<Vec<_>>();
+    let limits: [fn(&MineralBase) -> usize; 2] = [MineralBase::saturation, MineralBase::oversaturation];
+    for limit in limits.iter() {
+        for (from, base) in bases.iter().enumerate() {
+            while surplus[from] > 0 && left > 0 {
+                let to = bases
+                    .iter()
+                    .filter(|b| shares[&b.hatch] < limit(b))
+                    .min_by(|a, b| {
+                        let a = a.position.distance(base.position);
+                        let b = b.position.distance(base.position);
+                        a.partial_cmp(&b).unwrap()
+                    });
+                match to {
+                    Some(to) => *shares.get_mut(&to.hatch).unwrap() += 1,
+                    None => break,
+                }
+                surplus[from] -= 1;
+                left -= 1;
+            }
+        }
+    }
+    shares
+}
+
+/// How many drones each patch should get when `workers` drones mine minerals. Close patches fill
+/// up to 2 first, then far ones, and only drones left over after that go third on close patches.
+pub fn mineral_targets(patches: &[MineralPatch], workers: usize) -> HashMap<u64, usize> {
+    let mut targets = patches.iter().map(|p| (p.tag, 0)).collect::<HashMap<_, _>>();
+    let mut left = workers;
+    let rounds = [(true, PATCH_SATURATION), (false, PATCH_SATURATION), (true, CLOSE_PATCH_OVERSATURATION)];
+    for &(close, limit) in rounds.iter() {
+        for patch in patches.iter().filter(|p| p.close == close) {
+            let target = targets.get_mut(&patch.tag).unwrap();
+            let add = limit.saturating_sub(*target).min(left);
+            *target += add;
+            left -= add;
+        }
+    }
+    targets
+}
+
+/// Pairs rows with columns so that the summed cost is minimal, using the Hungarian algorithm.
+/// Every row gets a column if there are enough of them, otherwise every column gets a row.
+pub fn min_cost_assignment(costs: &[Vec<f32>]) -> Vec<Option<usize>> {
+    let rows = costs.len();
+    let cols = costs.first().map_or(0, |r| r.len());
+    if rows == 0 || cols == 0 {
+        return vec![None; rows];
+    }
+    if rows > cols {
+        let transposed = (0..cols)
+            .map(|c| (0..rows).map(|r| costs[r][c]).collect())
+            .collect::<Vec<_>>();
+        let mut result = vec![None; rows];
+        for (col, row) in min_cost_assignment(&transposed).into_iter().enumerate() {
+            if let Some(row) = row {
+                result[row] = Some(col);
+            }
+        }
+        return result;
+    }
+    // Potentials and matching are 1-indexed, with index 0 as the unmatched sentinel
+    let mut u = vec![0.0f64; rows + 1];
+    let mut v = vec![0.0f64; cols + 1];
+    let mut row_of_col = vec![0usize; cols + 1];
+    let mut way = vec![0usize; cols + 1];
+    for row in 1..=rows {
+        row_of_col[0] = row;
+        let mut col0 = 0;
+        let mut min_slack = vec![f64::INFINITY; cols + 1];
+        let mut used = vec![false; cols + 1];
+        loop {
+            used[col0] = true;
+            let row0 = row_of_col[col0];
+            let mut delta = f64::INFINITY;
+            let mut col1 = 0;
+            for col in 1..=cols {
+                if used[col] {
+                    continue;
+                }
+                let slack = costs[row0 - 1][col - 1] as f64 - u[row0] - v[col];
+                if slack < min_slack[col] {
+                    min_slack[col] = slack;
+                    way[col] = col0;
+                }
+                if min_slack[col] < delta {
+                    delta = min_slack[col];
+                    col1 = col;
+                }
+            }
+            for col in 0..=cols {
+                if used[col] {
+                    u[row_of_col[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    min_slack[col] -= delta;
+                }
+            }
+            col0 = col1;
+            if row_of_col[col0] == 0 {
+                break;
+            }
+        }
+        while col0 != 0 {
+            let col1 = way[col0];
+            row_of_col[col0] = row_of_col[col1];
+            col0 = col1;
+        }
+    }
+    let mut result = vec![None; rows];
+    for col in 1..=cols {
+        if row_of_col[col] != 0 {
+            result[row_of_col[col] - 1] = Some(col - 1);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::workers::{
+        mark_close_patches, min_cost_assignment, mineral_shares, mineral_targets, DroneTask, MineralBase,
+        MineralPatch, ResourceType, WorkerManager,
+    };
+    use rust_sc2::prelude::Point2;
+
+    const HATCH: u64 = 100;
+    const PATCH: u64 = 200;
+    const GAS: u64 = 300;
+
+    fn manager_with_drones(tags: &[u64]) -> WorkerManager {
+        let mut manager = WorkerManager::default();
+        for &tag in tags {
+            manager.registry.register(tag, Point2::new(0.0, 0.0), 0);
+            assert!(manager.add(tag));
+        }
+        manager
+    }
+
+    #[test]
+    fn assign_and_release() {
+        let mut manager = manager_with_drones(&[1, 2, 3]);
+        assert!(!manager.add(1));
+        manager.assign_gather(1, HATCH, ResourceType::Mineral, PATCH);
+        manager.assign_gather(2, HATCH, ResourceType::Mineral, PATCH);
+        manager.reserve_for_building(3, 10);
+        assert_eq!(manager.on_resource(PATCH).len(), 2);
+        assert_eq!(manager.count_at_base(HATCH, ResourceType::Mineral), 2);
+        assert_eq!(manager.task(3), Some(DroneTask::Construct { queued_at: 10 }));
+        manager.release(2);
+        assert_eq!(manager.idle(), vec![2]);
+        assert_eq!(manager.on_resource(PATCH), vec![1]);
+    }
+
+    #[test]
+    fn gas_drones_stay_inside_extractor_until_destroyed() {
+        let mut manager = manager_with_drones(&[1, 2]);
+        manager.assign_gather(1, HATCH, ResourceType::Gas, GAS);
+        manager.assign_gather(2, HATCH, ResourceType::Mineral, PATCH);
+        // Both disappear on a long step, drone 1 last seen well away from the extractor
+        let vanished = manager.registry.observe(&[], 1);
+        manager.handle_vanished(&vanished, &[GAS]);
+        assert_eq!(manager.task(2), None);
+        for iteration in 2..10 {
+            assert!(manager.registry.observe(&[], iteration).is_empty());
+            manager.retain_registered();
+            assert_eq!(manager.gas_workers_by_extractor().get(&GAS), Some(&1));
+        }
+        // It comes back out, goes in again, and dies in there
+        assert!(manager.registry.observe(&[(1, Point2::new(1.0, 1.0))], 10).is_empty());
+        let vanished = manager.registry.observe(&[], 11);
+        manager.handle_vanished(&vanished, &[GAS]);
+        assert!(manager.registry.contains(1));
+        manager.remove(1);
+        assert_eq!(manager.gas_workers_by_extractor().get(&GAS), None);
+    }
+
+    #[test]
+    fn gas_drones_without_extractor_are_gone() {
+        let mut manager = manager_with_drones(&[1]);
+        manager.assign_gather(1, HATCH, ResourceType::Gas, GAS);
+        let vanished = manager.registry.observe(&[], 1);
+        manager.handle_vanished(&vanished, &[]);
+        assert!(!manager.registry.contains(1));
+        assert_eq!(manager.task(1), None);
+    }
+
+    #[test]
+    fn gas_workers_survive_inside_extractor() {
+        let mut manager = manager_with_drones(&[1, 2]);
+        manager.assign_gather(1, HATCH, ResourceType::Gas, GAS);
+        manager.assign_gather(2, HATCH, ResourceType::Gas, GAS);
+        // Drone 1 goes into the extractor and disappears
+        let vanished = manager.registry.observe(&[(2, Point2::new(1.0, 1.0))], 1);
+        assert_eq!(vanished, vec![1]);
+        manager.registry.enter_gas(1, GAS);
+        assert!(manager.registry.observe(&[(2, Point2::new(1.0, 1.0))], 2).is_empty());
+        manager.retain_registered();
+        assert_eq!(manager.gas_workers_by_extractor().get(&GAS), Some(&2));
+        // And drone 2 dies
+        manager.remove(2);
+        assert_eq!(manager.gas_workers_by_extractor().get(&GAS), Some(&1));
+        assert_eq!(manager.task(2), None);
+    }
+
+    fn mineral_line() -> Vec<MineralPatch> {
+        let mut patches = (0..8)
+            .map(|i| MineralPatch {
+                tag: i,
+                hatch: HATCH,
+                position: Point2::new(i as f32, 0.0),
+                distance: if i % 2 == 0 { 6.0 } else { 7.2 },
+                close: false,
+            })
+            .collect::<Vec<_>>();
+        mark_close_patches(&mut patches);
+        patches
+    }
+
+    #[test]
+    fn close_patches_fill_first() {
+        let patches = mineral_line();
+        assert_eq!(patches.iter().filter(|p| p.close).count(), 4);
+        let targets = mineral_targets(&patches, 10);
+        for patch in patches.iter() {
+            assert_eq!(targets[&patch.tag], if patch.close || patch.tag == 1 { 2 } else { 0 });
+        }
+        // Oversaturation only happens on close patches once every patch has two
+        let targets = mineral_targets(&patches, 22);
+        for patch in patches.iter() {
+            assert_eq!(targets[&patch.tag], if patch.close { 3 } else { 2 });
+        }
+        assert_eq!(mineral_targets(&patches, 30).values().sum::<usize>(), 20);
+    }
+
+    fn base(hatch: u64, x: f32, patches: usize, workers: usize) -> MineralBase {
+        MineralBase {
+            hatch,
+            position: Point2::new(x, 0.0),
+            patches,
+            close_patches: patches / 2,
+            workers,
+        }
+    }
+
+    #[test]
+    fn shares_keep_drones_at_their_base() {
+        let bases = vec![base(1, 0.0, 8, 12), base(2, 40.0, 8, 6)];
+        let shares = mineral_shares(&bases, 18);
+        assert_eq!((shares[&1], shares[&2]), (12, 6));
+        // Fewer drones for minerals come off the fuller base
+        let shares = mineral_shares(&bases, 10);
+        assert_eq!((shares[&1], shares[&2]), (5, 5));
+    }
+
+    #[test]
+    fn shares_move_surplus_to_the_closest_base() {
+        let bases = vec![base(1, 0.0, 8, 24), base(2, 80.0, 8, 0), base(3, 30.0, 7, 2)];
+        let shares = mineral_shares(&bases, 26);
+        assert_eq!((shares[&1], shares[&2], shares[&3]), (16, 0, 10));
+        let shares = mineral_shares(&bases, 22);
+        assert_eq!((shares[&1], shares[&2], shares[&3]), (16, 0, 6));
+    }
+
+    #[test]
+    fn shares_oversaturate_once_every_base_is_full() {
+        let bases = vec![base(1, 0.0, 4, 12), base(2, 30.0, 3, 6)];
+        let shares = mineral_shares(&bases, 18);
+        // Base 1 keeps two of its spare drones on its close patches, base 2's one close patch
+        // takes another, and the last drone has nowhere to go
+        assert_eq!((shares[&1], shares[&2]), (10, 7));
+    }
+
+    #[test]
+    fn assignment_minimises_total_cost() {
+        // Greedily taking the cheapest pair first (row 0 to col 0) costs 1 + 10 = 11, the
+        // optimum is 2 + 3 = 5
+        let costs = vec![vec![1.0, 2.0], vec![3.0, 10.0]];
+        assert_eq!(min_cost_assignment(&costs), vec![Some(1), Some(0)]);
+        // More rows than columns leaves the most expensive row out
+        let costs = vec![vec![5.0], vec![1.0], vec![3.0]];
+        assert_eq!(min_cost_assignment(&costs), vec![None, Some(0), None]);
+        assert_eq!(min_cost_assignment(&[vec![], vec![]]), vec![None, None]);
+    }
+}